@@ -1,6 +1,7 @@
 import type { InitOutput } from "diff-wasm";
 import init, {
 	build_diff_tree_for_package,
+	close_diff_session,
 	get_diff_for_path,
 	prefetch_package,
 } from "diff-wasm";
@@ -8,6 +9,7 @@ import init, {
 import wasmUrl from "diff-wasm/diff_wasm_bg.wasm?url";
 
 let wasmPromise: Promise<InitOutput> | null = null;
+let sessionId: number | null = null;
 
 export function ensureWasmInitialized(): Promise<InitOutput> {
 	if (!wasmPromise) {
@@ -32,11 +34,65 @@ export type DiffFileEntry = {
 	oldPath?: string;
 	type: "file" | "directory";
 	status: DiffStatus;
+	binary?: boolean;
 	added?: number;
 	removed?: number;
 	children?: DiffFileEntry[];
 };
 
+export type InlineLineChange = {
+	tag: "delete" | "insert";
+	line: number;
+	spans: { start: number; end: number }[];
+};
+
+export type PackageErrorKind =
+	| "unsupportedRegistry"
+	| "invalidCoordinates"
+	| "notFound"
+	| "http"
+	| "network"
+	| "invalidMetadata"
+	| "noArtifact"
+	| "decompression"
+	| "invalidArchive"
+	| "missingEntry"
+	| "limitExceeded";
+
+export type PackageError = {
+	kind: PackageErrorKind;
+	message: string;
+	registry?: string;
+	package?: string;
+	version?: string;
+	status?: number;
+	cause?: string;
+	retryable: boolean;
+};
+
+function isPackageError(error: unknown): error is PackageError {
+	return typeof error === "object" && error !== null && "kind" in error;
+}
+
+function postError(error: unknown, fallback: string) {
+	if (isPackageError(error)) {
+		postMessage({
+			type: "error",
+			error: error.message,
+			kind: error.kind,
+			retryable: error.retryable,
+		});
+		return;
+	}
+	const message =
+		error instanceof Error
+			? error.message
+			: typeof error === "string"
+				? error
+				: fallback;
+	postMessage({ type: "error", error: message });
+}
+
 type WorkerRequest =
 	| {
 			type: "start-diff";
@@ -62,9 +118,7 @@ self.onmessage = async (event: MessageEvent<WorkerRequest>) => {
 	try {
 		await ensureWasmInitialized();
 	} catch (error) {
-		const message =
-			error instanceof Error ? error.message : "WASM initialization failed";
-		postMessage({ type: "error", error: message });
+		postError(error, "WASM initialization failed");
 		return;
 	}
 
@@ -87,24 +141,27 @@ async function handleStartDiff(
 ) {
 	try {
 		const start = performance.now();
-		const diffTree = await build_diff_tree_for_package(
+		const result = (await build_diff_tree_for_package(
 			registry,
 			pkg,
 			from,
 			to,
 			0.75,
-		);
+			undefined,
+		)) as { sessionId: number; tree: DiffFileEntry };
 		const end = performance.now();
 
 		console.log(`Building diff tree took ${(end - start).toFixed(2)}ms`);
 
+		if (sessionId !== null) close_diff_session(sessionId);
+		sessionId = result.sessionId;
+
 		postMessage({
 			type: "diff-result",
-			data: diffTree,
+			data: result.tree,
 		});
 	} catch (error) {
-		const message = error instanceof Error ? error.message : "Unknown error";
-		postMessage({ type: "error", error: message });
+		postError(error, "Unknown error");
 	}
 }
 
@@ -126,19 +183,26 @@ async function handlePrefetch(
 
 export function handleGetDiff(filename: string, oldPath?: string) {
 	try {
-		const result = get_diff_for_path(filename, oldPath) as {
+		if (sessionId === null) throw new Error("No active diff session");
+		const result = get_diff_for_path(
+			sessionId,
+			filename,
+			oldPath,
+			undefined,
+			undefined,
+		) as {
 			data: string;
 			isDiff: boolean;
+			inlineChanges?: InlineLineChange[];
 		};
 		postMessage({
 			type: "diff-result",
 			filename,
 			data: result.data,
 			isDiff: result.isDiff,
+			inlineChanges: result.inlineChanges ?? [],
 		});
 	} catch (error) {
-		const message =
-			error instanceof Error ? error.message : "Diff generation failed";
-		postMessage({ type: "error", error: message });
+		postError(error, "Diff generation failed");
 	}
 }
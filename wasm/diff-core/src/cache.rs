@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::types::{FileContent, FileMapEntry};
+
+pub const DEFAULT_CACHE_BUDGET_BYTES: usize = 256 * 1024 * 1024;
+
+/// Rough per-entry bookkeeping cost on top of path and content bytes.
+const ENTRY_OVERHEAD_BYTES: usize = 64;
+
+struct CachedPackage {
+    files: Rc<HashMap<String, FileMapEntry>>,
+    size_bytes: usize,
+    last_used: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub budget_bytes: usize,
+    pub used_bytes: usize,
+    pub entries: Vec<CacheEntryStats>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntryStats {
+    pub key: String,
+    pub size_bytes: usize,
+    pub pinned: bool,
+}
+
+/// Extracted packages keyed by `cache_key`, evicted least-recently-used first
+/// once their estimated size exceeds the budget. Pinned keys are never evicted.
+pub struct ExtractionCache {
+    packages: HashMap<String, CachedPackage>,
+    budget_bytes: usize,
+    used_bytes: usize,
+    clock: u64,
+}
+
+impl ExtractionCache {
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            packages: HashMap::new(),
+            budget_bytes,
+            used_bytes: 0,
+            clock: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<&Rc<HashMap<String, FileMapEntry>>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.packages.get_mut(key).map(|package| {
+            package.last_used = clock;
+            &package.files
+        })
+    }
+
+    pub fn insert(
+        &mut self,
+        key: String,
+        files: Rc<HashMap<String, FileMapEntry>>,
+        pinned: &HashSet<String>,
+    ) {
+        self.clock += 1;
+        let size_bytes = Self::estimate_size(&files);
+        let package = CachedPackage {
+            files,
+            size_bytes,
+            last_used: self.clock,
+        };
+        if let Some(previous) = self.packages.insert(key.clone(), package) {
+            self.used_bytes -= previous.size_bytes;
+        }
+        self.used_bytes += size_bytes;
+
+        // The package just inserted is about to be used, so it survives even
+        // when it alone exceeds the budget.
+        let mut pinned = pinned.clone();
+        pinned.insert(key);
+        self.evict(&pinned);
+    }
+
+    pub fn set_budget(&mut self, budget_bytes: usize, pinned: &HashSet<String>) {
+        self.budget_bytes = budget_bytes;
+        self.evict(pinned);
+    }
+
+    /// Drops every package that is not pinned.
+    pub fn clear(&mut self, pinned: &HashSet<String>) {
+        self.packages.retain(|key, _| pinned.contains(key));
+        self.used_bytes = self.packages.values().map(|package| package.size_bytes).sum();
+    }
+
+    pub fn stats(&self, pinned: &HashSet<String>) -> CacheStats {
+        let mut entries: Vec<_> = self
+            .packages
+            .iter()
+            .map(|(key, package)| CacheEntryStats {
+                key: key.clone(),
+                size_bytes: package.size_bytes,
+                pinned: pinned.contains(key),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+        CacheStats {
+            budget_bytes: self.budget_bytes,
+            used_bytes: self.used_bytes,
+            entries,
+        }
+    }
+
+    fn evict(&mut self, pinned: &HashSet<String>) {
+        while self.used_bytes > self.budget_bytes {
+            let victim = self
+                .packages
+                .iter()
+                .filter(|(key, _)| !pinned.contains(*key))
+                .min_by_key(|(_, package)| package.last_used)
+                .map(|(key, _)| key.clone());
+
+            let Some(victim) = victim else {
+                break;
+            };
+            if let Some(package) = self.packages.remove(&victim) {
+                self.used_bytes -= package.size_bytes;
+            }
+        }
+    }
+
+    fn estimate_size(files: &HashMap<String, FileMapEntry>) -> usize {
+        files
+            .iter()
+            .map(|(path, entry)| path.len() + content_size(&entry.content) + ENTRY_OVERHEAD_BYTES)
+            .sum()
+    }
+}
+
+/// Lossily decoded text keeps its raw bytes alongside, so it is held twice.
+fn content_size(content: &FileContent) -> usize {
+    match content {
+        FileContent::Lossy { raw, text } => raw.len() + text.len(),
+        _ => content.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Estimated size of `package(100)`.
+    const SIZE: usize = 1 + 100 + ENTRY_OVERHEAD_BYTES;
+
+    fn package(len: usize) -> Rc<HashMap<String, FileMapEntry>> {
+        let entry = FileMapEntry::file(vec![b'x'; len]);
+        Rc::new(HashMap::from([("f".to_string(), entry)]))
+    }
+
+    fn pins(keys: &[&str]) -> HashSet<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    fn keys(cache: &ExtractionCache) -> Vec<String> {
+        let stats = cache.stats(&HashSet::new());
+        stats.entries.into_iter().map(|entry| entry.key).collect()
+    }
+
+    fn used_bytes(cache: &ExtractionCache) -> usize {
+        cache.stats(&HashSet::new()).used_bytes
+    }
+
+    #[test]
+    fn get_marks_a_package_as_recently_used() {
+        let mut cache = ExtractionCache::new(2 * SIZE);
+        cache.insert("a".to_string(), package(100), &pins(&[]));
+        cache.insert("b".to_string(), package(100), &pins(&[]));
+        assert!(cache.get("a").is_some());
+        cache.insert("c".to_string(), package(100), &pins(&[]));
+        assert_eq!(keys(&cache), ["a", "c"]);
+    }
+
+    #[test]
+    fn evicts_only_unpinned_packages() {
+        let pinned = pins(&["a"]);
+        let mut cache = ExtractionCache::new(2 * SIZE);
+        cache.insert("a".to_string(), package(100), &pinned);
+        cache.insert("b".to_string(), package(100), &pinned);
+        cache.insert("c".to_string(), package(100), &pinned);
+        assert_eq!(keys(&cache), ["a", "c"]);
+    }
+
+    #[test]
+    fn keeps_an_inserted_package_that_alone_exceeds_the_budget() {
+        let mut cache = ExtractionCache::new(SIZE);
+        cache.insert("a".to_string(), package(100), &pins(&[]));
+        cache.insert("big".to_string(), package(1000), &pins(&[]));
+        assert_eq!(keys(&cache), ["big"]);
+        assert_eq!(used_bytes(&cache), SIZE + 900);
+    }
+
+    #[test]
+    fn tracks_used_bytes_across_reinserts_and_clear() {
+        let pinned = pins(&["a"]);
+        let mut cache = ExtractionCache::new(10 * SIZE);
+        cache.insert("a".to_string(), package(100), &pinned);
+        cache.insert("a".to_string(), package(200), &pinned);
+        assert_eq!(used_bytes(&cache), SIZE + 100);
+        cache.insert("b".to_string(), package(100), &pinned);
+        assert_eq!(used_bytes(&cache), 2 * SIZE + 100);
+
+        cache.clear(&pinned);
+        assert_eq!(keys(&cache), ["a"]);
+        assert_eq!(used_bytes(&cache), SIZE + 100);
+    }
+
+    #[test]
+    fn shrinking_the_budget_evicts_down_to_it() {
+        let pinned = pins(&["b"]);
+        let mut cache = ExtractionCache::new(3 * SIZE);
+        for key in ["a", "b", "c"] {
+            cache.insert(key.to_string(), package(100), &pinned);
+        }
+        assert!(cache.get("a").is_some());
+
+        cache.set_budget(2 * SIZE, &pinned);
+        assert_eq!(keys(&cache), ["a", "b"]);
+        cache.set_budget(0, &pinned);
+        assert_eq!(keys(&cache), ["b"]);
+        assert_eq!(used_bytes(&cache), SIZE);
+    }
+}
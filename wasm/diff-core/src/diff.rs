@@ -0,0 +1,1153 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::rc::Rc;
+use similar::{
+    capture_diff_slices, capture_diff_slices_by_key, group_diff_ops, Algorithm, ChangeTag,
+    DiffOp, DiffTag, DiffableStr, InlineChangeMode, InlineChangeOptions, TextDiff,
+};
+use sha2::{Digest, Sha256};
+use crate::types::{
+    BinaryDiff, DiffAlgorithm, DiffFileEntry, DiffHunk, DiffLine, DiffOptions, DiffStatus,
+    FileContent, FileMapEntry, FileType, InlineLineChange, InlineMode, InlineSpan, LineTag,
+};
+
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// Unified diff of one file. `old_path` names the file on the from side, which
+/// differs from `new_path` for renames.
+pub fn get_diff_content(
+    old_path: &str,
+    new_path: &str,
+    from_content: Option<&str>,
+    to_content: Option<&str>,
+    context_lines: usize,
+    options: &DiffOptions,
+) -> String {
+    let options = DiffOptions {
+        inline_mode: InlineMode::None,
+        ..options.clone()
+    };
+    let hunks = get_diff_hunks(
+        from_content.unwrap_or(""),
+        to_content.unwrap_or(""),
+        context_lines,
+        &options,
+    );
+    render_unified_diff(old_path, new_path, from_content.is_some(), to_content.is_some(), &hunks)
+}
+
+pub fn render_unified_diff(
+    old_path: &str,
+    new_path: &str,
+    from_exists: bool,
+    to_exists: bool,
+    hunks: &[DiffHunk],
+) -> String {
+    let mut result = String::new();
+    if hunks.is_empty() {
+        return result;
+    }
+
+    if from_exists {
+        result.push_str(&format!("--- from/{old_path}\n"));
+    } else {
+        result.push_str("--- /dev/null\n");
+    }
+    if to_exists {
+        result.push_str(&format!("+++ to/{new_path}\n"));
+    } else {
+        result.push_str("+++ /dev/null\n");
+    }
+
+    for hunk in hunks {
+        result.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            format_hunk_range(hunk.old_start, hunk.old_lines),
+            format_hunk_range(hunk.new_start, hunk.new_lines)
+        ));
+        for line in &hunk.lines {
+            result.push(match line.tag {
+                LineTag::Equal => ' ',
+                LineTag::Delete => '-',
+                LineTag::Insert => '+',
+            });
+            result.push_str(&line.content);
+            result.push('\n');
+            if line.missing_newline {
+                result.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    result
+}
+
+fn format_hunk_range(start: u32, lines: u32) -> String {
+    if lines == 1 {
+        start.to_string()
+    } else {
+        format!("{start},{lines}")
+    }
+}
+
+pub fn get_diff_hunks(
+    from_content: &str,
+    to_content: &str,
+    context_lines: usize,
+    options: &DiffOptions,
+) -> Vec<DiffHunk> {
+    let diff = LineDiff::new(from_content, to_content, options);
+    let mut hunks = Vec::new();
+
+    for group in diff.grouped_ops(context_lines) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_lines = (last.old_range().end - first.old_range().start) as u32;
+        let new_lines = (last.new_range().end - first.new_range().start) as u32;
+        let mut hunk = DiffHunk {
+            old_start: first.old_range().start as u32 + u32::from(old_lines > 0),
+            old_lines,
+            new_start: first.new_range().start as u32 + u32::from(new_lines > 0),
+            new_lines,
+            lines: Vec::new(),
+        };
+
+        for op in &group {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            let mut spans = match tag {
+                DiffTag::Replace => diff.inline_spans(old_range.clone(), new_range.clone()),
+                _ => HashMap::new(),
+            };
+
+            if tag == DiffTag::Equal {
+                for (old_index, new_index) in old_range.zip(new_range) {
+                    // Context lines come from the old side so the patch applies to it.
+                    hunk.lines.push(diff_line(
+                        LineTag::Equal,
+                        diff.old_lines[old_index],
+                        Some(old_index),
+                        Some(new_index),
+                    ));
+                }
+                continue;
+            }
+            for old_index in old_range {
+                let mut line =
+                    diff_line(LineTag::Delete, diff.old_lines[old_index], Some(old_index), None);
+                line.spans = spans.remove(&(LineTag::Delete, old_index));
+                hunk.lines.push(line);
+            }
+            for new_index in new_range {
+                let mut line =
+                    diff_line(LineTag::Insert, diff.new_lines[new_index], None, Some(new_index));
+                line.spans = spans.remove(&(LineTag::Insert, new_index));
+                hunk.lines.push(line);
+            }
+        }
+        hunks.push(hunk);
+    }
+    hunks
+}
+
+/// Whether a file pair is summarized instead of diffed line by line: either side is
+/// binary, or both decode to the same lossy text even though their bytes differ.
+pub fn needs_binary_summary(from: Option<&FileContent>, to: Option<&FileContent>) -> bool {
+    if from.is_some_and(FileContent::is_binary) || to.is_some_and(FileContent::is_binary) {
+        return true;
+    }
+    match (from, to) {
+        (Some(from), Some(to)) => {
+            from.as_bytes() != to.as_bytes() && from.as_text() == to.as_text()
+        }
+        _ => false,
+    }
+}
+
+/// Size and SHA-256 summary of a file pair that cannot be diffed line by line.
+pub fn get_binary_diff(from: Option<&[u8]>, to: Option<&[u8]>) -> BinaryDiff {
+    BinaryDiff {
+        old_size: from.map(|bytes| bytes.len() as u64),
+        new_size: to.map(|bytes| bytes.len() as u64),
+        old_hash: from.map(sha256_hex),
+        new_hash: to.map(sha256_hex),
+    }
+}
+
+/// Git-style notice shown in place of a unified diff for binary files.
+pub fn render_binary_notice(
+    old_path: &str,
+    new_path: &str,
+    from_exists: bool,
+    to_exists: bool,
+) -> String {
+    let label = |exists: bool, side: &str, path: &str| {
+        if exists {
+            format!("{side}/{path}")
+        } else {
+            "/dev/null".to_string()
+        }
+    };
+    format!(
+        "Binary files {} and {} differ\n",
+        label(from_exists, "from", old_path),
+        label(to_exists, "to", new_path)
+    )
+}
+
+/// Hex-encoded SHA-256 digest.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Presents an unchanged file as a single hunk of context lines.
+pub fn get_unchanged_hunk(content: &str) -> Option<DiffHunk> {
+    let lines: Vec<DiffLine> = content
+        .tokenize_lines()
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| diff_line(LineTag::Equal, value, Some(index), Some(index)))
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    let count = lines.len() as u32;
+    Some(DiffHunk {
+        old_start: 1,
+        old_lines: count,
+        new_start: 1,
+        new_lines: count,
+        lines,
+    })
+}
+
+fn diff_line(
+    tag: LineTag,
+    value: &str,
+    old_index: Option<usize>,
+    new_index: Option<usize>,
+) -> DiffLine {
+    DiffLine {
+        tag,
+        old_line: old_index.map(|i| i as u32 + 1),
+        new_line: new_index.map(|i| i as u32 + 1),
+        content: value.strip_suffix('\n').unwrap_or(value).to_string(),
+        missing_newline: !value.ends_with('\n'),
+        spans: None,
+    }
+}
+
+/// Line diff whose ops may compare normalized lines while indexing the original ones.
+struct LineDiff<'a> {
+    old_lines: Vec<&'a str>,
+    new_lines: Vec<&'a str>,
+    ops: Vec<DiffOp>,
+    options: &'a DiffOptions,
+}
+
+impl<'a> LineDiff<'a> {
+    fn new(from: &'a str, to: &'a str, options: &'a DiffOptions) -> Self {
+        let old_lines = from.tokenize_lines();
+        let new_lines = to.tokenize_lines();
+        let algorithm = similar_algorithm(options.algorithm);
+        let ops = if compares_lines_verbatim(options) {
+            capture_diff_slices(algorithm, &old_lines, &new_lines)
+        } else {
+            capture_diff_slices_by_key(algorithm, &old_lines, &new_lines, |line| {
+                line_key(line, options)
+            })
+        };
+        Self {
+            old_lines,
+            new_lines,
+            ops,
+            options,
+        }
+    }
+
+    /// Returns (added, removed, unchanged) line counts.
+    fn line_counts(&self) -> (u32, u32, u32) {
+        let mut added = 0;
+        let mut removed = 0;
+        let mut unchanged = 0;
+        for op in &self.ops {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            if tag == DiffTag::Equal {
+                unchanged += old_range.len() as u32;
+                continue;
+            }
+            removed += old_range.filter(|&i| !self.is_ignorable(self.old_lines[i])).count() as u32;
+            added += new_range.filter(|&i| !self.is_ignorable(self.new_lines[i])).count() as u32;
+        }
+        (added, removed, unchanged)
+    }
+
+    fn grouped_ops(&self, context_lines: usize) -> Vec<Vec<DiffOp>> {
+        let mut groups = group_diff_ops(self.ops.clone(), context_lines);
+        if self.options.ignore_blank_lines {
+            groups.retain(|group| group.iter().any(|op| self.has_significant_change(op)));
+        }
+        groups
+    }
+
+    fn has_significant_change(&self, op: &DiffOp) -> bool {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        tag != DiffTag::Equal
+            && (old_range.into_iter().any(|i| !self.is_ignorable(self.old_lines[i]))
+                || new_range.into_iter().any(|i| !self.is_ignorable(self.new_lines[i])))
+    }
+
+    fn is_ignorable(&self, line: &str) -> bool {
+        self.options.ignore_blank_lines && line.trim().is_empty()
+    }
+
+    /// Inline spans for a replaced region, keyed by line tag and index.
+    fn inline_spans(
+        &self,
+        old_range: Range<usize>,
+        new_range: Range<usize>,
+    ) -> HashMap<(LineTag, usize), Vec<InlineSpan>> {
+        let mut result = HashMap::new();
+        let Some(inline_options) = inline_change_options(self.options.inline_mode) else {
+            return result;
+        };
+
+        let (old_offset, new_offset) = (old_range.start, new_range.start);
+        let diff = TextDiff::configure()
+            .algorithm(similar_algorithm(self.options.algorithm))
+            .diff_slices(&self.old_lines[old_range], &self.new_lines[new_range]);
+        for op in diff.ops() {
+            if op.tag() != DiffTag::Replace {
+                continue;
+            }
+            // No deadline: `Instant::now` is unavailable on wasm32-unknown-unknown.
+            for change in diff.iter_inline_changes_with_options_deadline(op, inline_options, None) {
+                let (key, value) = match (change.tag(), change.old_index(), change.new_index()) {
+                    (ChangeTag::Delete, Some(i), _) => {
+                        ((LineTag::Delete, old_offset + i), self.old_lines[old_offset + i])
+                    }
+                    (ChangeTag::Insert, _, Some(i)) => {
+                        ((LineTag::Insert, new_offset + i), self.new_lines[new_offset + i])
+                    }
+                    _ => continue,
+                };
+                let spans = emphasized_spans(change.values(), value);
+                if !spans.is_empty() {
+                    result.insert(key, spans);
+                }
+            }
+        }
+        result
+    }
+}
+
+fn compares_lines_verbatim(options: &DiffOptions) -> bool {
+    !(options.ignore_whitespace || options.ignore_whitespace_amount || options.normalize_eol)
+}
+
+/// Comparison key for a line under the whitespace and line-ending options.
+fn line_key(line: &str, options: &DiffOptions) -> String {
+    let (body, terminator) = match line.strip_suffix("\r\n") {
+        Some(body) => (body, "\r\n"),
+        None => match line.strip_suffix(['\n', '\r']) {
+            Some(body) => (body, &line[body.len()..]),
+            None => (line, ""),
+        },
+    };
+    let terminator = if options.normalize_eol && !terminator.is_empty() {
+        "\n"
+    } else {
+        terminator
+    };
+
+    let mut key = if options.ignore_whitespace {
+        body.chars().filter(|c| !c.is_whitespace()).collect()
+    } else if options.ignore_whitespace_amount {
+        collapse_whitespace(body)
+    } else {
+        body.to_string()
+    };
+    if options.ignore_whitespace || options.ignore_whitespace_amount {
+        // Trailing `\r` counts as whitespace in these modes, so only presence matters.
+        if !terminator.is_empty() {
+            key.push('\n');
+        }
+    } else {
+        key.push_str(terminator);
+    }
+    key
+}
+
+/// Collapses whitespace runs to a single space and drops trailing whitespace.
+fn collapse_whitespace(body: &str) -> String {
+    let mut result = String::with_capacity(body.len());
+    let mut in_whitespace = false;
+    for c in body.trim_end().chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                result.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            result.push(c);
+            in_whitespace = false;
+        }
+    }
+    result
+}
+
+fn similar_algorithm(algorithm: DiffAlgorithm) -> Algorithm {
+    match algorithm {
+        DiffAlgorithm::Myers => Algorithm::Myers,
+        DiffAlgorithm::Patience => Algorithm::Patience,
+        DiffAlgorithm::Lcs => Algorithm::Lcs,
+        DiffAlgorithm::Histogram => Algorithm::Histogram,
+    }
+}
+
+fn inline_change_options(mode: InlineMode) -> Option<InlineChangeOptions> {
+    let mut options = InlineChangeOptions::new();
+    match mode {
+        InlineMode::None => return None,
+        InlineMode::Words => options.mode(InlineChangeMode::Words),
+        InlineMode::Chars => options.mode(InlineChangeMode::Chars),
+    };
+    Some(options)
+}
+
+/// Merges emphasized tokens into byte ranges, clipped before the line terminator.
+fn emphasized_spans(values: &[(bool, &str)], content: &str) -> Vec<InlineSpan> {
+    let content_len = content.trim_end_matches('\r').len() as u32;
+    let mut spans: Vec<InlineSpan> = Vec::new();
+    let mut offset = 0;
+    for &(emphasized, value) in values {
+        let start = offset;
+        offset += value.len() as u32;
+        let end = offset.min(content_len);
+        if !emphasized || end <= start {
+            continue;
+        }
+        match spans.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => spans.push(InlineSpan { start, end }),
+        }
+    }
+    spans
+}
+
+/// Inline spans of the changed lines in `hunks`, for callers that also render
+/// the same hunks as a unified diff.
+pub fn get_inline_changes(hunks: &[DiffHunk]) -> Vec<InlineLineChange> {
+    hunks
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter_map(|line| {
+            let spans = line.spans.clone()?;
+            let (tag, number) = match line.tag {
+                LineTag::Delete => (LineTag::Delete, line.old_line?),
+                LineTag::Insert => (LineTag::Insert, line.new_line?),
+                LineTag::Equal => return None,
+            };
+            Some(InlineLineChange {
+                tag,
+                line: number,
+                spans,
+            })
+        })
+        .collect()
+}
+
+pub struct DiffTreeBuilder {
+    from_files: Rc<HashMap<String, FileMapEntry>>,
+    to_files: Rc<HashMap<String, FileMapEntry>>,
+    from_file_paths: HashSet<String>,
+    to_file_paths: HashSet<String>,
+    from_dirs: HashSet<String>,
+    to_dirs: HashSet<String>,
+    similarity_threshold: f64,
+    options: DiffOptions,
+}
+
+impl DiffTreeBuilder {
+    pub fn new(similarity_threshold: f64) -> Self {
+        Self {
+            from_files: Rc::default(),
+            to_files: Rc::default(),
+            from_file_paths: HashSet::new(),
+            to_file_paths: HashSet::new(),
+            from_dirs: HashSet::new(),
+            to_dirs: HashSet::new(),
+            similarity_threshold: similarity_threshold.clamp(0.0, 1.0),
+            options: DiffOptions::default(),
+        }
+    }
+
+    pub fn set_options(&mut self, options: DiffOptions) {
+        self.options = options;
+    }
+
+    pub fn set_from_files(&mut self, files: Rc<HashMap<String, FileMapEntry>>) {
+        self.from_files = files;
+        self.from_file_paths = self.collect_file_paths(&self.from_files);
+        self.from_dirs = self.collect_directories(&self.from_files);
+    }
+
+    pub fn set_to_files(&mut self, files: Rc<HashMap<String, FileMapEntry>>) {
+        self.to_files = files;
+        self.to_file_paths = self.collect_file_paths(&self.to_files);
+        self.to_dirs = self.collect_directories(&self.to_files);
+    }
+
+    pub fn build_tree(&self) -> DiffFileEntry {
+        // 1. Identify added/removed files
+        let from_paths: HashSet<_> = self.from_files.keys().cloned().collect();
+        let to_paths: HashSet<_> = self.to_files.keys().cloned().collect();
+
+        let deleted: Vec<_> = self
+            .from_file_paths
+            .difference(&self.to_file_paths)
+            .cloned()
+            .collect();
+        let added: Vec<_> = self
+            .to_file_paths
+            .difference(&self.from_file_paths)
+            .cloned()
+            .collect();
+
+        // 2. Detect renames
+        let renames = self.detect_renames_optimized(&deleted, &added);
+
+        // 3. Build tree structure
+        let tree =
+            self.build_tree_structure(&from_paths, &to_paths, &self.from_dirs, &self.to_dirs);
+
+        // 4. Compute statuses and counts
+        self.compute_tree_stats(tree, &renames)
+    }
+
+    fn detect_renames_optimized(
+        &self,
+        deleted: &[String],
+        added: &[String],
+    ) -> HashMap<String, String> {
+        let mut renames = HashMap::new();
+        let mut used = HashSet::new();
+
+        // Phase 1: Exact content matches using hash-based lookup
+        let mut del_by_hash: HashMap<u64, Vec<&String>> = HashMap::new();
+        for del_path in deleted {
+            if let Some(content) = self.file_content(&self.from_files, del_path) {
+                let hash = Self::hash_content(content.as_bytes());
+                del_by_hash
+                    .entry(hash)
+                    .or_default()
+                    .push(del_path);
+            }
+        }
+
+        for add_path in added {
+            if let Some(add_content) = self.file_content(&self.to_files, add_path) {
+                let hash = Self::hash_content(add_content.as_bytes());
+
+                if let Some(candidates) = del_by_hash.get(&hash) {
+                    for del_path in candidates {
+                        if used.contains(*del_path) {
+                            continue;
+                        }
+
+                        if let Some(del_content) = self.file_content(&self.from_files, del_path) {
+                            if add_content.as_bytes() == del_content.as_bytes() {
+                                renames.insert(add_path.clone(), (*del_path).clone());
+                                used.insert((*del_path).clone());
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Phase 2: Similar content with multi-stage filtering
+
+        // Pre-compute line sets for Jaccard similarity (fast pre-filter)
+        let mut del_line_sets: HashMap<&String, HashSet<&str>> = HashMap::new();
+        for del_path in deleted {
+            if used.contains(del_path) {
+                continue;
+            }
+            if let Some(content) = self.text_content(&self.from_files, del_path) {
+                del_line_sets.insert(del_path, content.lines().collect());
+            }
+        }
+
+        for add_path in added {
+            if renames.contains_key(add_path) {
+                continue;
+            }
+
+            let add_content = match self.text_content(&self.to_files, add_path) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            let add_lines: HashSet<&str> = add_content.lines().collect();
+            let add_name = add_path.split('/').next_back().unwrap_or("");
+            let mut best: Option<(String, f64)> = None;
+
+            for del_path in deleted {
+                if used.contains(del_path) {
+                    continue;
+                }
+
+                let del_content = match self.text_content(&self.from_files, del_path) {
+                    Some(c) => c,
+                    None => continue,
+                };
+
+                // Filter 1: Length ratio check (very fast)
+                if !self.can_be_similar(del_content, add_content) {
+                    continue;
+                }
+
+                // Filter 2: Jaccard similarity on line sets (fast)
+                let del_lines = del_line_sets.get(del_path).unwrap();
+                let jaccard = self.jaccard_similarity(&add_lines, del_lines);
+
+                // Early reject if Jaccard is too low (threshold * 0.7 as heuristic)
+                if jaccard < self.similarity_threshold * 0.7 {
+                    continue;
+                }
+
+                // Filter 3: Expensive diff-based similarity (only for promising candidates)
+                let similarity = self.calculate_similarity(del_content, add_content);
+
+                // Filename boost
+                let del_name = del_path.split('/').next_back().unwrap_or("");
+                let adjusted = if add_name == del_name {
+                    similarity * 1.2
+                } else {
+                    similarity
+                };
+
+                if adjusted >= self.similarity_threshold {
+                    if let Some((_, best_sim)) = &best {
+                        if adjusted > *best_sim {
+                            best = Some((del_path.clone(), adjusted));
+                        }
+                    } else {
+                        best = Some((del_path.clone(), adjusted));
+                    }
+                }
+            }
+
+            if let Some((from_path, _)) = best {
+                renames.insert(add_path.clone(), from_path.clone());
+                used.insert(from_path);
+            }
+        }
+
+        renames
+    }
+
+    fn jaccard_similarity(&self, set1: &HashSet<&str>, set2: &HashSet<&str>) -> f64 {
+        if set1.is_empty() && set2.is_empty() {
+            return 1.0;
+        }
+
+        let intersection = set1.intersection(set2).count();
+        let union = set1.len() + set2.len() - intersection;
+
+        if union == 0 {
+            return 0.0;
+        }
+
+        intersection as f64 / union as f64
+    }
+
+    fn can_be_similar(&self, from: &str, to: &str) -> bool {
+        let len_ratio = from.len() as f64 / to.len().max(1) as f64;
+        len_ratio >= self.similarity_threshold && len_ratio <= 1.0 / self.similarity_threshold
+    }
+
+    fn hash_content(content: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn calculate_similarity(&self, from: &str, to: &str) -> f64 {
+        if from == to {
+            return 1.0;
+        }
+        if from.is_empty() || to.is_empty() {
+            return 0.0;
+        }
+
+        let (added, removed, unchanged) = LineDiff::new(from, to, &self.options).line_counts();
+
+        let total = (added + removed + unchanged).max(1);
+        unchanged as f64 / total as f64
+    }
+
+    fn build_tree_structure(
+        &self,
+        from_paths: &HashSet<String>,
+        to_paths: &HashSet<String>,
+        from_dirs: &HashSet<String>,
+        to_dirs: &HashSet<String>,
+    ) -> DiffFileEntry {
+        // Merge all paths
+        let mut all_paths = HashSet::new();
+        all_paths.extend(from_paths.iter().cloned());
+        all_paths.extend(to_paths.iter().cloned());
+        all_paths.extend(from_dirs.iter().cloned());
+        all_paths.extend(to_dirs.iter().cloned());
+
+        let mut nodes: HashMap<String, DiffFileEntry> = HashMap::new();
+        let mut children_map: HashMap<String, Vec<String>> = HashMap::new();
+
+        for path in &all_paths {
+            if path == "/" {
+                continue;
+            }
+            let file_type = self.resolve_file_type(path);
+
+            nodes.insert(
+                path.clone(),
+                DiffFileEntry {
+                    path: path.clone(),
+                    old_path: None,
+                    file_type,
+                    status: DiffStatus::Unchanged,
+                    binary: false,
+                    added: None,
+                    removed: None,
+                    children: Some(Vec::new()),
+                },
+            );
+
+            let parent = Self::parent_path(path);
+            children_map
+                .entry(parent)
+                .or_default()
+                .push(path.clone());
+        }
+
+        let mut root = DiffFileEntry {
+            path: "/".to_string(),
+            old_path: None,
+            file_type: FileType::Directory,
+            status: DiffStatus::Unchanged,
+            binary: false,
+            added: None,
+            removed: None,
+            children: Some(Vec::new()),
+        };
+
+        root.children = Some(Self::build_children("/", &mut nodes, &mut children_map));
+        root
+    }
+
+    fn collect_directories(&self, entries: &HashMap<String, FileMapEntry>) -> HashSet<String> {
+        let mut dirs = HashSet::new();
+
+        for (path, entry) in entries {
+            // Add directory entries
+            if matches!(entry.file_type, FileType::Directory) {
+                dirs.insert(path.clone());
+            }
+
+            // Add parent directories
+            if let Some(last_slash) = path.rfind('/') {
+                let mut end = last_slash;
+                while end > 0 {
+                    if let Some(slash_pos) = path[..end].rfind('/') {
+                        dirs.insert(path[..end].to_string());
+                        end = slash_pos;
+                    } else {
+                        // Add the first component if not root
+                        if end > 0 {
+                            dirs.insert(path[..end].to_string());
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        dirs
+    }
+
+    fn build_children(
+        parent: &str,
+        nodes: &mut HashMap<String, DiffFileEntry>,
+        children_map: &mut HashMap<String, Vec<String>>,
+    ) -> Vec<DiffFileEntry> {
+        let mut child_paths = match children_map.remove(parent) {
+            Some(paths) => paths,
+            None => return Vec::new(),
+        };
+
+        child_paths.sort();
+        let mut children = Vec::with_capacity(child_paths.len());
+
+        for child_path in child_paths {
+            let mut node = match nodes.remove(&child_path) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            let nested = Self::build_children(&child_path, nodes, children_map);
+            node.children = Some(nested);
+            children.push(node);
+        }
+
+        children
+    }
+
+    fn parent_path(path: &str) -> String {
+        if let Some(last_slash) = path.rfind('/') {
+            if last_slash == 0 {
+                "/".to_string()
+            } else {
+                path[..last_slash].to_string()
+            }
+        } else {
+            "/".to_string()
+        }
+    }
+
+    fn compute_tree_stats(
+        &self,
+        mut root: DiffFileEntry,
+        renames: &HashMap<String, String>,
+    ) -> DiffFileEntry {
+        self.compute_node_stats(&mut root, renames, &self.from_dirs, &self.to_dirs);
+        root
+    }
+
+    fn compute_node_stats(
+        &self,
+        node: &mut DiffFileEntry,
+        renames: &HashMap<String, String>,
+        from_dirs: &HashSet<String>,
+        to_dirs: &HashSet<String>,
+    ) -> (u32, u32) {
+        match node.file_type {
+            FileType::File => {
+                // Check if this file is a rename
+                if let Some(old_path) = renames.get(&node.path) {
+                    node.status = DiffStatus::Renamed;
+                    node.old_path = Some(old_path.clone());
+
+                    // Calculate diff stats
+                    let from_content = self.file_content(&self.from_files, old_path);
+                    let to_content = self.file_content(&self.to_files, &node.path);
+
+                    if let (Some(from), Some(to)) = (from_content, to_content) {
+                        node.binary = from.is_binary() || to.is_binary();
+                        let (added, removed) = self.count_changes(from, to);
+                        node.added = Some(added);
+                        node.removed = Some(removed);
+                        return (added, removed);
+                    }
+                }
+
+                let from_content = self.file_content(&self.from_files, &node.path);
+                let to_content = self.file_content(&self.to_files, &node.path);
+
+                match (from_content, to_content) {
+                    (Some(from), Some(to)) => {
+                        node.binary = from.is_binary() || to.is_binary();
+                        let (added, removed) = self.count_changes(from, to);
+                        // Decide from the bytes, since binary files have no line counts and
+                        // lossy text can hide a change. Text files whose line diff is empty
+                        // only because of the whitespace options count as unchanged.
+                        let changed = from.as_bytes() != to.as_bytes()
+                            && (node.binary
+                                || from.as_text() == to.as_text()
+                                || added > 0
+                                || removed > 0);
+                        node.status = if changed {
+                            DiffStatus::Modified
+                        } else {
+                            DiffStatus::Unchanged
+                        };
+                        node.added = Some(added);
+                        node.removed = Some(removed);
+                        (added, removed)
+                    }
+                    (Some(from), None) => {
+                        node.status = DiffStatus::Removed;
+                        node.binary = from.is_binary();
+                        let removed = Self::line_count(from);
+                        node.added = Some(0);
+                        node.removed = Some(removed);
+                        (0, removed)
+                    }
+                    (None, Some(to)) => {
+                        node.status = DiffStatus::Added;
+                        node.binary = to.is_binary();
+                        let added = Self::line_count(to);
+                        node.added = Some(added);
+                        node.removed = Some(0);
+                        (added, 0)
+                    }
+                    (None, None) => {
+                        node.status = DiffStatus::Unchanged;
+                        node.added = Some(0);
+                        node.removed = Some(0);
+                        (0, 0)
+                    }
+                }
+            }
+            FileType::Directory => {
+                // Recursively compute stats for children
+                let mut total_added = 0;
+                let mut total_removed = 0;
+                let mut all_unchanged = true;
+
+                if let Some(ref mut children) = node.children {
+                    for child in children.iter_mut() {
+                        let (added, removed) =
+                            self.compute_node_stats(child, renames, from_dirs, to_dirs);
+                        total_added += added;
+                        total_removed += removed;
+
+                        if !matches!(child.status, DiffStatus::Unchanged) {
+                            all_unchanged = false;
+                        }
+                    }
+                }
+
+                node.added = Some(total_added);
+                node.removed = Some(total_removed);
+
+                // Determine directory status
+                let in_from = node.path == "/" || from_dirs.contains(&node.path);
+                let in_to = node.path == "/" || to_dirs.contains(&node.path);
+
+                if !in_from && in_to {
+                    node.status = DiffStatus::Added;
+                } else if in_from && !in_to {
+                    node.status = DiffStatus::Removed;
+                } else if all_unchanged {
+                    node.status = DiffStatus::Unchanged;
+                } else {
+                    node.status = DiffStatus::Modified;
+                }
+
+                (total_added, total_removed)
+            }
+        }
+    }
+
+    /// Added and removed line counts; binary files always report none.
+    fn count_changes(&self, from: &FileContent, to: &FileContent) -> (u32, u32) {
+        if from.as_bytes() == to.as_bytes() {
+            return (0, 0);
+        }
+        match (from.as_text(), to.as_text()) {
+            (Some(from), Some(to)) => self.count_diff(from, to),
+            _ => (0, 0),
+        }
+    }
+
+    fn count_diff(&self, from: &str, to: &str) -> (u32, u32) {
+        let (added, removed, _) = LineDiff::new(from, to, &self.options).line_counts();
+        (added, removed)
+    }
+
+    fn line_count(content: &FileContent) -> u32 {
+        content.as_text().map_or(0, |text| text.lines().count() as u32)
+    }
+
+    fn collect_file_paths(&self, entries: &HashMap<String, FileMapEntry>) -> HashSet<String> {
+        entries
+            .iter()
+            .filter_map(|(path, entry)| {
+                if matches!(entry.file_type, FileType::File) {
+                    Some(path.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn resolve_file_type(&self, path: &str) -> FileType {
+        if let Some(entry) = self.from_files.get(path).or_else(|| self.to_files.get(path)) {
+            return entry.file_type.clone();
+        }
+
+        // Paths without an entry of their own are implied parent directories.
+        FileType::Directory
+    }
+
+    fn file_content<'a>(
+        &self,
+        entries: &'a HashMap<String, FileMapEntry>,
+        path: &str,
+    ) -> Option<&'a FileContent> {
+        entries.get(path).and_then(|entry| {
+            if matches!(entry.file_type, FileType::File) {
+                Some(&entry.content)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn text_content<'a>(
+        &self,
+        entries: &'a HashMap<String, FileMapEntry>,
+        path: &str,
+    ) -> Option<&'a str> {
+        self.file_content(entries, path).and_then(FileContent::as_text)
+    }
+}
+
+pub fn build_diff_tree(
+    from_files: Rc<HashMap<String, FileMapEntry>>,
+    to_files: Rc<HashMap<String, FileMapEntry>>,
+    similarity_threshold: f64,
+    options: DiffOptions,
+) -> DiffFileEntry {
+    let mut builder = DiffTreeBuilder::new(similarity_threshold);
+    builder.set_options(options);
+    builder.set_from_files(from_files);
+    builder.set_to_files(to_files);
+    builder.build_tree()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unified(
+        from: Option<&str>,
+        to: Option<&str>,
+        context: usize,
+        options: &DiffOptions,
+    ) -> String {
+        get_diff_content("a.txt", "a.txt", from, to, context, options)
+    }
+
+    fn numbered_lines(count: u32) -> String {
+        (1..=count).map(|n| format!("{n}\n")).collect()
+    }
+
+    #[test]
+    fn renders_headers_and_hunk_ranges() {
+        let diff = unified(Some("1\n2\n3\n"), Some("1\ntwo\n3\n"), 3, &DiffOptions::default());
+        assert_eq!(diff, "--- from/a.txt\n+++ to/a.txt\n@@ -1,3 +1,3 @@\n 1\n-2\n+two\n 3\n");
+    }
+
+    #[test]
+    fn limits_context_and_splits_distant_changes() {
+        let from = numbered_lines(20);
+        let to = from.replace("5\n", "five\n").replace("15\n", "fifteen\n");
+        let diff = unified(Some(&from), Some(&to), 1, &DiffOptions::default());
+        let headers: Vec<&str> = diff.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(headers, ["@@ -4,3 +4,3 @@", "@@ -14,3 +14,3 @@"]);
+    }
+
+    #[test]
+    fn uses_dev_null_and_empty_ranges_for_added_files() {
+        let diff = unified(None, Some("x\ny\n"), 3, &DiffOptions::default());
+        assert_eq!(diff, "--- /dev/null\n+++ to/a.txt\n@@ -0,0 +1,2 @@\n+x\n+y\n");
+        let diff = unified(Some("x\n"), None, 3, &DiffOptions::default());
+        assert_eq!(diff, "--- from/a.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-x\n");
+    }
+
+    #[test]
+    fn marks_missing_trailing_newline() {
+        let diff = unified(Some("a\n"), Some("a"), 3, &DiffOptions::default());
+        assert_eq!(
+            diff,
+            "--- from/a.txt\n+++ to/a.txt\n@@ -1 +1 @@\n-a\n+a\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn names_the_old_path_for_renames() {
+        let options = DiffOptions::default();
+        let diff = get_diff_content("old.txt", "new.txt", Some("a\n"), Some("b\n"), 3, &options);
+        assert!(diff.starts_with("--- from/old.txt\n+++ to/new.txt\n"));
+    }
+
+    #[test]
+    fn whitespace_and_eol_options_hide_matching_changes() {
+        let ignore = |options: DiffOptions, from: &str, to: &str| {
+            unified(Some(from), Some(to), 3, &options).is_empty()
+        };
+        assert!(!ignore(DiffOptions::default(), "a b\n", "a  b\n"));
+        let amount = DiffOptions { ignore_whitespace_amount: true, ..DiffOptions::default() };
+        assert!(ignore(amount.clone(), "a b\n", "a  b \n"));
+        assert!(!ignore(amount, "a b\n", "ab\n"));
+        let all = DiffOptions { ignore_whitespace: true, ..DiffOptions::default() };
+        assert!(ignore(all, "a b\n", "ab\n"));
+        let blank = DiffOptions { ignore_blank_lines: true, ..DiffOptions::default() };
+        assert!(ignore(blank, "a\nb\n", "a\n\nb\n"));
+        let eol = DiffOptions { normalize_eol: true, ..DiffOptions::default() };
+        assert!(!ignore(DiffOptions::default(), "a\nb\n", "a\r\nb\r\n"));
+        assert!(ignore(eol, "a\nb\n", "a\r\nb\r\n"));
+    }
+
+    #[test]
+    fn inline_spans_cover_changed_words_or_chars() {
+        let spans = |inline_mode: InlineMode, from: &str, to: &str| {
+            let options = DiffOptions { inline_mode, ..DiffOptions::default() };
+            let hunks = get_diff_hunks(from, to, 3, &options);
+            let changes = get_inline_changes(&hunks).into_iter().map(|change| {
+                let ranges = change.spans.iter().map(|span| (span.start, span.end));
+                (change.tag, change.line, ranges.collect::<Vec<_>>())
+            });
+            changes.collect::<Vec<_>>()
+        };
+        assert_eq!(
+            spans(InlineMode::Words, "one two three\n", "one 2 three\n"),
+            [(LineTag::Delete, 1, vec![(4, 7)]), (LineTag::Insert, 1, vec![(4, 5)])]
+        );
+        assert_eq!(
+            spans(InlineMode::Chars, "color: red\n", "colour: rod\n"),
+            [(LineTag::Delete, 1, vec![(8, 9)]), (LineTag::Insert, 1, vec![(4, 5), (9, 10)])]
+        );
+        assert!(spans(InlineMode::None, "one two\n", "one 2\n").is_empty());
+    }
+
+    fn files(entries: &[(&str, &str)]) -> Rc<HashMap<String, FileMapEntry>> {
+        let entry = |text: &str| FileMapEntry::file(text.as_bytes().to_vec());
+        Rc::new(entries.iter().map(|(path, text)| (path.to_string(), entry(text))).collect())
+    }
+
+    fn find<'a>(entry: &'a DiffFileEntry, path: &str) -> Option<&'a DiffFileEntry> {
+        if entry.path == path {
+            return Some(entry);
+        }
+        entry.children.iter().flatten().find_map(|child| find(child, path))
+    }
+
+    #[test]
+    fn detects_exact_and_similar_renames() {
+        let original = numbered_lines(60);
+        let edited = format!("{original}61\n");
+        let from = files(&[("old.txt", &original), ("copy.txt", "same\n"), ("gone.txt", "x\n")]);
+        let to = files(&[("new.txt", &edited), ("sub/copy.txt", "same\n"), ("fresh.txt", "y\n")]);
+        let tree = build_diff_tree(from, to, 0.75, DiffOptions::default());
+
+        let renamed = find(&tree, "new.txt").unwrap();
+        assert!(matches!(renamed.status, DiffStatus::Renamed));
+        assert_eq!(renamed.old_path.as_deref(), Some("old.txt"));
+        assert_eq!((renamed.added, renamed.removed), (Some(1), Some(0)));
+
+        let moved = find(&tree, "sub/copy.txt").unwrap();
+        assert!(matches!(moved.status, DiffStatus::Renamed));
+        assert_eq!(moved.old_path.as_deref(), Some("copy.txt"));
+
+        // Unrelated files stay an addition and a removal.
+        assert!(matches!(find(&tree, "fresh.txt").unwrap().status, DiffStatus::Added));
+        assert!(matches!(find(&tree, "gone.txt").unwrap().status, DiffStatus::Removed));
+    }
+}
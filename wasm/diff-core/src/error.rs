@@ -0,0 +1,123 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// What went wrong while fetching or extracting a package, coarse enough for the
+/// UI to pick a message and decide whether retrying can help.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    UnsupportedRegistry,
+    /// The package name or version is malformed for its registry.
+    InvalidCoordinates,
+    /// The registry has no such package or version.
+    NotFound,
+    /// The registry answered with an unexpected HTTP status.
+    Http,
+    /// The request never got a response.
+    Network,
+    /// Registry metadata could not be parsed.
+    InvalidMetadata,
+    /// The registry lists no artifact we can download.
+    NoArtifact,
+    Decompression,
+    /// The tar or zip structure is corrupt.
+    InvalidArchive,
+    /// A member the package format requires, such as a gem's `data.tar.gz`, is absent.
+    MissingEntry,
+    /// Extraction stopped at a size, entry-count or compression-ratio cap.
+    LimitExceeded,
+}
+
+/// The package an error belongs to. Serialized inline with the error fields.
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageCoordinates {
+    pub registry: String,
+    pub package: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Boxed to keep `Result<_, PackageError>` small on the happy path.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub coordinates: Option<Box<PackageCoordinates>>,
+    /// HTTP status code, for `NotFound` and `Http` errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// The underlying error, as reported by the library that raised it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>,
+    /// Whether the same request may succeed later.
+    pub retryable: bool,
+}
+
+impl PackageError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            coordinates: None,
+            status: None,
+            cause: None,
+            retryable: kind == ErrorKind::Network,
+        }
+    }
+
+    /// Classifies a failed HTTP response by its status code.
+    pub fn http_status(url: &str, status: u16) -> Self {
+        let (kind, message) = match status {
+            404 | 410 => (ErrorKind::NotFound, format!("Not found: {url}")),
+            _ => (ErrorKind::Http, format!("Failed to fetch {url} (HTTP {status})")),
+        };
+        let mut error = Self::new(kind, message);
+        error.status = Some(status);
+        error.retryable = status == 408 || status == 429 || status >= 500;
+        error
+    }
+
+    pub fn with_cause(mut self, cause: impl fmt::Display) -> Self {
+        self.cause = Some(cause.to_string());
+        self
+    }
+
+    /// Records which package the error belongs to, unless already set.
+    pub fn for_package(mut self, registry: &str, package: &str, version: &str) -> Self {
+        self.coordinates.get_or_insert_with(|| {
+            Box::new(PackageCoordinates {
+                registry: registry.to_string(),
+                package: package.to_string(),
+                version: version.to_string(),
+            })
+        });
+        self
+    }
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(coordinates) = &self.coordinates {
+            let PackageCoordinates { registry, package, version } = &**coordinates;
+            write!(f, "{registry}:{package}@{version}: ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(cause) = &self.cause {
+            write!(f, ": {cause}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PackageError {}
+
+/// Adapter for `map_err` that keeps the original error as the cause.
+pub fn caused_by<E: fmt::Display>(
+    kind: ErrorKind,
+    message: impl Into<String>,
+) -> impl FnOnce(E) -> PackageError {
+    let message = message.into();
+    move |err| PackageError::new(kind, message).with_cause(err)
+}
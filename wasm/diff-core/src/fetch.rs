@@ -0,0 +1,104 @@
+//! HTTP access for registry downloads.
+//!
+//! The `wasm` feature fetches through the browser (window or worker) when built for
+//! wasm32, and the `native` feature uses a blocking client elsewhere. Builds with
+//! neither still compile, but every download fails.
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub use self::web::fetch_bytes;
+
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+pub use self::native::fetch_bytes;
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+mod web {
+    use js_sys::Uint8Array;
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::{Response, Window, WorkerGlobalScope};
+
+    use crate::error::{ErrorKind, PackageError};
+
+    pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>, PackageError> {
+        let network_error = |err: JsValue| {
+            PackageError::new(ErrorKind::Network, format!("Failed to fetch {url}"))
+                .with_cause(js_error(err))
+        };
+        let fetch_promise = fetch_with_str(url)?;
+        let resp_value = JsFuture::from(fetch_promise).await.map_err(network_error)?;
+        let resp: Response = resp_value.dyn_into().map_err(network_error)?;
+        if !resp.ok() {
+            return Err(PackageError::http_status(url, resp.status()));
+        }
+
+        let buffer = JsFuture::from(resp.array_buffer().map_err(network_error)?)
+            .await
+            .map_err(network_error)?;
+        let array = Uint8Array::new(&buffer);
+        let mut bytes = vec![0; array.length() as usize];
+        array.copy_to(&mut bytes);
+        Ok(bytes)
+    }
+
+    fn fetch_with_str(url: &str) -> Result<js_sys::Promise, PackageError> {
+        let global = js_sys::global();
+        if let Some(window) = global.dyn_ref::<Window>() {
+            Ok(window.fetch_with_str(url))
+        } else if let Some(worker) = global.dyn_ref::<WorkerGlobalScope>() {
+            Ok(worker.fetch_with_str(url))
+        } else {
+            Err(PackageError::new(
+                ErrorKind::Network,
+                "Global scope does not support fetch",
+            ))
+        }
+    }
+
+    fn js_error(value: JsValue) -> String {
+        value.as_string().unwrap_or_else(|| format!("{value:?}"))
+    }
+}
+
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+mod native {
+    use std::io::Read;
+
+    use crate::error::{caused_by, ErrorKind, PackageError};
+
+    /// Blocks on the request; callers drive the future with a simple executor.
+    pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>, PackageError> {
+        let response = match ureq::get(url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, _)) => {
+                return Err(PackageError::http_status(url, status));
+            }
+            Err(err) => {
+                let message = format!("Failed to fetch {url}");
+                return Err(PackageError::new(ErrorKind::Network, message).with_cause(err));
+            }
+        };
+        let mut bytes = Vec::new();
+        let message = format!("Failed to read response from {url}");
+        response
+            .into_reader()
+            .read_to_end(&mut bytes)
+            .map_err(caused_by(ErrorKind::Network, message))?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(not(any(
+    all(feature = "wasm", target_arch = "wasm32"),
+    all(feature = "native", not(target_arch = "wasm32"))
+)))]
+pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>, crate::error::PackageError> {
+    use crate::error::{ErrorKind, PackageError};
+
+    let mut error = PackageError::new(
+        ErrorKind::Network,
+        format!("Cannot fetch {url}: built without the `wasm` or `native` feature"),
+    );
+    // This build cannot make requests at all, so retrying never helps.
+    error.retryable = false;
+    Err(error)
+}
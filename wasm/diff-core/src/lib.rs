@@ -0,0 +1,10 @@
+//! Platform-independent package diffing: archive extraction, diff trees and
+//! line diffs. Registry downloads go through the `wasm` or `native` feature.
+
+pub mod types;
+pub mod cache;
+pub mod diff;
+pub mod error;
+pub mod fetch;
+pub mod package;
+pub mod provenance;
@@ -0,0 +1,1373 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Cursor, Read, Write};
+
+use flate2::read::GzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use serde::Deserialize;
+use tar::Archive;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::error::{caused_by, ErrorKind, PackageError};
+use crate::fetch::fetch_bytes;
+use crate::types::{ExtractionLimits, FileMapEntry, FileType};
+
+#[derive(Deserialize)]
+struct PyPiResponse {
+    urls: Vec<PyPiUrl>,
+}
+
+#[derive(Deserialize)]
+struct PyPiUrl {
+    url: String,
+    packagetype: String,
+}
+
+#[derive(Deserialize)]
+struct PackagistResponse {
+    packages: HashMap<String, Vec<PackagistVersion>>,
+}
+
+#[derive(Deserialize)]
+struct PackagistVersion {
+    version: String,
+    #[serde(default)]
+    version_normalized: Option<String>,
+    /// An object with a `url`, or the string `"__unset"` in minified metadata.
+    #[serde(default)]
+    dist: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct PubPackageResponse {
+    versions: Vec<PubVersion>,
+}
+
+#[derive(Deserialize)]
+struct PubVersion {
+    version: String,
+    archive_url: String,
+}
+
+/// One file from the anaconda.org package listing.
+#[derive(Deserialize)]
+struct CondaFile {
+    basename: String,
+    version: String,
+    attrs: CondaFileAttrs,
+}
+
+#[derive(Deserialize)]
+struct CondaFileAttrs {
+    subdir: String,
+    build: String,
+    #[serde(default)]
+    build_number: u64,
+}
+
+pub async fn fetch_and_extract_package(
+    registry: &str,
+    pkg: &str,
+    version: &str,
+    limits: &ExtractionLimits,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let budget = ExtractionBudget::new(limits);
+    let files = async {
+        if registry == "maven" {
+            return fetch_maven_artifact(pkg, version, &budget).await;
+        }
+        let bytes = match registry {
+            "pypi" => fetch_pypi_sdist_bytes(pkg, version).await?,
+            "packagist" => fetch_packagist_dist_bytes(pkg, version).await?,
+            "pub" => fetch_pub_archive_bytes(pkg, version).await?,
+            "conda" => fetch_conda_package_bytes(pkg, version).await?,
+            _ => {
+                let url = build_tarball_url(registry, pkg, version)?;
+                fetch_bytes(&url).await?
+            }
+        };
+        budget.add_input(bytes.len() as u64);
+        match registry {
+            "rubygems" => extract_gem_bytes(&bytes, &budget),
+            "go" => extract_go_module_zip(&bytes, pkg, version, &budget),
+            "nuget" => extract_nupkg_bytes(&bytes, &budget),
+            "hex" => extract_hex_bytes(&bytes, &budget),
+            // pub.dev archives are flat, with pubspec.yaml at the top level.
+            "pub" => extract_archive_entries(&bytes, &budget),
+            "conda" => extract_conda_bytes(&bytes, &budget),
+            _ => extract_archive_bytes(&bytes, &budget),
+        }
+    };
+    files.await.map_err(|err| err.for_package(registry, pkg, version))
+}
+
+/// Extracts an archive that was read from disk rather than fetched from a registry.
+pub fn extract_local_archive(
+    file_name: &str,
+    bytes: &[u8],
+    limits: &ExtractionLimits,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let budget = ExtractionBudget::new(limits);
+    budget.add_input(bytes.len() as u64);
+    let file_name = file_name.to_ascii_lowercase();
+    if file_name.ends_with(".gem") {
+        extract_gem_bytes(bytes, &budget)
+    } else if file_name.ends_with(".nupkg") {
+        extract_nupkg_bytes(bytes, &budget)
+    } else if file_name.ends_with(".conda") {
+        extract_conda_bytes(bytes, &budget)
+    } else {
+        extract_archive_bytes(bytes, &budget)
+    }
+}
+
+fn build_tarball_url(registry: &str, pkg: &str, version: &str) -> Result<String, PackageError> {
+    match registry {
+        "npm" => {
+            let unscoped = pkg.split('/').nth(1).unwrap_or(pkg);
+            Ok(format!(
+                "https://registry.npmjs.org/{pkg}/-/{unscoped}-{version}.tgz"
+            ))
+        }
+        "crates" => Ok(format!(
+            "https://static.crates.io/crates/{pkg}/{pkg}-{version}.crate"
+        )),
+
+        "rubygems" => Ok(format!("https://api.diffpack.io/api/download?package={pkg}&version={version}&registry=rubygems")),
+        "nuget" => {
+            let id = pkg.to_lowercase();
+            let version = version.to_lowercase();
+            Ok(format!(
+                "https://api.nuget.org/v3-flatcontainer/{id}/{version}/{id}.{version}.nupkg"
+            ))
+        }
+        "hex" => Ok(format!("https://repo.hex.pm/tarballs/{pkg}-{version}.tar")),
+        // Git hosts serve any tag, branch or commit as a tarball of the tree.
+        "github" => {
+            let (owner, repo) = split_repo_path(pkg)
+                .filter(|(owner, _)| !owner.contains('/'))
+                .ok_or_else(|| invalid_repo_path(pkg, "owner/repo"))?;
+            Ok(format!("https://codeload.github.com/{owner}/{repo}/tar.gz/{version}"))
+        }
+        "gitlab" => {
+            let (_, name) =
+                split_repo_path(pkg).ok_or_else(|| invalid_repo_path(pkg, "group/project"))?;
+            Ok(format!(
+                "https://gitlab.com/{pkg}/-/archive/{version}/{name}-{version}.tar.gz"
+            ))
+        }
+        "go" => Ok(format!(
+            "https://proxy.golang.org/{}/@v/{}.zip",
+            escape_go_path(pkg),
+            escape_go_path(version)
+        )),
+        _ => Err(PackageError::new(
+            ErrorKind::UnsupportedRegistry,
+            format!("Unsupported registry: {registry}"),
+        )),
+    }
+}
+
+/// Splits `owner/repo` (or `group/subgroup/project`) at the last slash.
+fn split_repo_path(pkg: &str) -> Option<(&str, &str)> {
+    pkg.rsplit_once('/')
+        .filter(|(owner, repo)| !owner.is_empty() && !repo.is_empty())
+}
+
+fn invalid_repo_path(pkg: &str, expected: &str) -> PackageError {
+    PackageError::new(
+        ErrorKind::InvalidCoordinates,
+        format!("Repositories are named {expected}, got {pkg}"),
+    )
+}
+
+/// Applies the module proxy's case encoding: each upper-case letter becomes `!`
+/// followed by its lower-case form, so paths stay unique on case-insensitive
+/// file systems.
+fn escape_go_path(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for ch in path.chars() {
+        if ch.is_ascii_uppercase() {
+            escaped.push('!');
+            escaped.push(ch.to_ascii_lowercase());
+        } else {
+            escaped.push(ch);
+        }
+    }
+    escaped
+}
+
+/// Fetches the sources jar of a `groupId:artifactId` coordinate, or the main jar
+/// when no sources were published, and adds the POM as a synthetic `pom.xml`.
+async fn fetch_maven_artifact(
+    pkg: &str,
+    version: &str,
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let base_url = maven_artifact_base_url(pkg, version)?;
+    let jar_bytes = match fetch_bytes(&format!("{base_url}-sources.jar")).await {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind == ErrorKind::NotFound => {
+            fetch_bytes(&format!("{base_url}.jar")).await?
+        }
+        Err(err) => return Err(err),
+    };
+    budget.add_input(jar_bytes.len() as u64);
+    // Jars have no wrapping directory, so the top level is kept as is.
+    let mut files = parse_zip_bytes(&jar_bytes, budget)?;
+
+    match fetch_bytes(&format!("{base_url}.pom")).await {
+        Ok(pom) => {
+            budget.add_file("pom.xml", pom.len() as u64)?;
+            files.insert("pom.xml".to_string(), FileMapEntry::file(pom));
+        }
+        Err(err) if err.kind == ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    Ok(files)
+}
+
+fn maven_artifact_base_url(pkg: &str, version: &str) -> Result<String, PackageError> {
+    let (group_id, artifact_id) = pkg
+        .split_once(':')
+        .filter(|(group_id, artifact_id)| !group_id.is_empty() && !artifact_id.is_empty())
+        .ok_or_else(|| {
+            PackageError::new(
+                ErrorKind::InvalidCoordinates,
+                format!("Maven packages are named groupId:artifactId, got {pkg}"),
+            )
+        })?;
+    let group_path = group_id.replace('.', "/");
+    Ok(format!(
+        "https://repo1.maven.org/maven2/{group_path}/{artifact_id}/{version}/{artifact_id}-{version}"
+    ))
+}
+
+async fn fetch_pypi_sdist_bytes(pkg: &str, version: &str) -> Result<Vec<u8>, PackageError> {
+    let metadata_url = format!("https://pypi.org/pypi/{pkg}/{version}/json");
+    let metadata_bytes = fetch_bytes(&metadata_url).await?;
+    let metadata: PyPiResponse = serde_json::from_slice(&metadata_bytes)
+        .map_err(caused_by(ErrorKind::InvalidMetadata, "Failed to parse PyPI metadata"))?;
+
+    let sdist_url = select_pypi_sdist_url(&metadata.urls)?;
+    fetch_bytes(&sdist_url).await
+}
+
+fn select_pypi_sdist_url(urls: &[PyPiUrl]) -> Result<String, PackageError> {
+    let mut sdist_supported = None;
+    let mut sdist_fallback = None;
+    let mut wheel_supported = None;
+    let mut wheel_fallback = None;
+
+    for entry in urls {
+        if entry.packagetype == "sdist" {
+            if is_supported_archive_url(&entry.url) {
+                if sdist_supported.is_none() {
+                    sdist_supported = Some(entry.url.clone());
+                }
+            } else if sdist_fallback.is_none() {
+                sdist_fallback = Some(entry.url.clone());
+            }
+        } else if entry.packagetype == "bdist_wheel" {
+            if is_supported_archive_url(&entry.url) {
+                if wheel_supported.is_none() {
+                    wheel_supported = Some(entry.url.clone());
+                }
+            } else if wheel_fallback.is_none() {
+                wheel_fallback = Some(entry.url.clone());
+            }
+        }
+    }
+
+    sdist_supported
+        .or(wheel_supported)
+        .or(sdist_fallback)
+        .or(wheel_fallback)
+        .ok_or_else(|| {
+            PackageError::new(
+                ErrorKind::NoArtifact,
+                "No downloadable artifacts found for PyPI package",
+            )
+        })
+}
+
+fn is_supported_archive_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    lower.ends_with(".tar.gz")
+        || lower.ends_with(".tgz")
+        || lower.ends_with(".tar")
+        || lower.ends_with(".tar.xz")
+        || lower.ends_with(".txz")
+        || lower.ends_with(".tar.bz2")
+        || lower.ends_with(".tbz2")
+        || lower.ends_with(".tar.zst")
+        || lower.ends_with(".zip")
+        || lower.ends_with(".whl")
+}
+
+/// Downloads the dist zip listed for `version` in the Composer v2 metadata.
+/// GitHub-hosted dists wrap everything in a `<vendor>-<name>-<sha>/` directory,
+/// which `strip_common_root` removes during extraction.
+async fn fetch_packagist_dist_bytes(pkg: &str, version: &str) -> Result<Vec<u8>, PackageError> {
+    let name = pkg.to_lowercase();
+    let metadata_url = format!("https://repo.packagist.org/p2/{name}.json");
+    let metadata_bytes = fetch_bytes(&metadata_url).await?;
+    let metadata: PackagistResponse = serde_json::from_slice(&metadata_bytes)
+        .map_err(caused_by(ErrorKind::InvalidMetadata, "Failed to parse Packagist metadata"))?;
+
+    let versions = metadata.packages.get(&name).map(Vec::as_slice).unwrap_or_default();
+    let dist_url = select_packagist_dist_url(versions, version)?;
+    fetch_bytes(&dist_url).await
+}
+
+fn select_packagist_dist_url(
+    versions: &[PackagistVersion],
+    version: &str,
+) -> Result<String, PackageError> {
+    // Tags are often published as `v1.2.3` while users ask for `1.2.3`, or vice versa.
+    let bare = version.trim_start_matches('v');
+    let entry = versions
+        .iter()
+        .find(|entry| entry.version == version)
+        .or_else(|| {
+            versions.iter().find(|entry| {
+                entry.version.trim_start_matches('v') == bare
+                    || entry.version_normalized.as_deref() == Some(version)
+            })
+        })
+        .ok_or_else(|| {
+            let message = format!("Version {version} not found on Packagist");
+            PackageError::new(ErrorKind::NotFound, message)
+        })?;
+
+    entry
+        .dist
+        .as_ref()
+        .and_then(|dist| dist.get("url"))
+        .and_then(serde_json::Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            PackageError::new(
+                ErrorKind::NoArtifact,
+                format!("No dist archive listed for version {version} on Packagist"),
+            )
+        })
+}
+
+async fn fetch_pub_archive_bytes(pkg: &str, version: &str) -> Result<Vec<u8>, PackageError> {
+    let metadata_url = format!("https://pub.dev/api/packages/{pkg}");
+    let metadata_bytes = fetch_bytes(&metadata_url).await?;
+    let metadata: PubPackageResponse = serde_json::from_slice(&metadata_bytes)
+        .map_err(caused_by(ErrorKind::InvalidMetadata, "Failed to parse pub.dev metadata"))?;
+
+    let archive_url = metadata
+        .versions
+        .into_iter()
+        .find(|entry| entry.version == version)
+        .map(|entry| entry.archive_url)
+        .ok_or_else(|| {
+            let message = format!("Version {version} not found on pub.dev");
+            PackageError::new(ErrorKind::NotFound, message)
+        })?;
+    fetch_bytes(&archive_url).await
+}
+
+/// Downloads a conda-forge build of `pkg`. The package may be qualified with a
+/// platform, as in `linux-64/numpy`; otherwise `noarch` is preferred, then
+/// `linux-64`. The version may carry a build string (`1.26.4-py312h8753938_0`)
+/// to pick one build, and otherwise the highest build number wins.
+async fn fetch_conda_package_bytes(pkg: &str, version: &str) -> Result<Vec<u8>, PackageError> {
+    let (subdir, name) = match pkg.split_once('/') {
+        Some((subdir, name)) => (Some(subdir), name),
+        None => (None, pkg),
+    };
+    if name.is_empty() || name.contains('/') || subdir.is_some_and(str::is_empty) {
+        return Err(PackageError::new(
+            ErrorKind::InvalidCoordinates,
+            format!("Conda package must be `name` or `platform/name`, got {pkg}"),
+        ));
+    }
+
+    let metadata_url = format!("https://api.anaconda.org/package/conda-forge/{name}/files");
+    let metadata_bytes = fetch_bytes(&metadata_url).await?;
+    let files: Vec<CondaFile> = serde_json::from_slice(&metadata_bytes)
+        .map_err(caused_by(ErrorKind::InvalidMetadata, "Failed to parse conda-forge metadata"))?;
+
+    let basename = select_conda_file(&files, subdir, version).ok_or_else(|| {
+        let platform = subdir.map(|subdir| format!(" for {subdir}")).unwrap_or_default();
+        let message = format!("Version {version} not found on conda-forge{platform}");
+        PackageError::new(ErrorKind::NotFound, message)
+    })?;
+    fetch_bytes(&format!("https://conda.anaconda.org/conda-forge/{basename}")).await
+}
+
+fn select_conda_file<'a>(
+    files: &'a [CondaFile],
+    subdir: Option<&str>,
+    version: &str,
+) -> Option<&'a str> {
+    let subdir_rank = |file: &CondaFile| match (subdir, file.attrs.subdir.as_str()) {
+        (Some(wanted), actual) => (wanted == actual).then_some(0),
+        (None, "noarch") => Some(0),
+        (None, "linux-64") => Some(1),
+        (None, _) => None,
+    };
+    files
+        .iter()
+        .filter(|file| {
+            file.version == version || format!("{}-{}", file.version, file.attrs.build) == version
+        })
+        .filter(|file| {
+            let name = file.basename.to_ascii_lowercase();
+            name.ends_with(".conda") || name.ends_with(".tar.bz2")
+        })
+        .filter_map(|file| subdir_rank(file).map(|rank| (rank, file)))
+        // Lowest subdir rank, then highest build number, then `.conda` over `.tar.bz2`.
+        .min_by_key(|(rank, file)| {
+            (*rank, std::cmp::Reverse(file.attrs.build_number), !file.basename.ends_with(".conda"))
+        })
+        .map(|(_, file)| file.basename.as_str())
+}
+
+/// A `.conda` package is a zip holding `info-*.tar.zst` (metadata) and
+/// `pkg-*.tar.zst` (payload); both are merged so `info/` sits beside the
+/// installed files, as in the legacy `.tar.bz2` format, which is a single tarball.
+fn extract_conda_bytes(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    if !is_zip(bytes) {
+        return extract_archive_entries(bytes, budget);
+    }
+
+    let mut files = HashMap::new();
+    let mut found_payload = false;
+    for (path, entry) in parse_zip_bytes(bytes, budget)? {
+        let is_inner_tarball = !path.contains('/')
+            && (path.starts_with("info-") || path.starts_with("pkg-"))
+            && path.ends_with(".tar.zst");
+        if !is_inner_tarball {
+            continue;
+        }
+        found_payload |= path.starts_with("pkg-");
+        files.extend(extract_archive_entries(entry.content.as_bytes(), budget)?);
+    }
+
+    if !found_payload {
+        return Err(PackageError::new(
+            ErrorKind::MissingEntry,
+            "pkg-*.tar.zst not found in conda package",
+        ));
+    }
+    Ok(files)
+}
+
+fn extract_gem_bytes(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let mut archive = Archive::new(Cursor::new(bytes));
+    let entries = archive
+        .entries()
+        .map_err(caused_by(ErrorKind::InvalidArchive, "Gem tar parsing failed"))?;
+
+    let mut data_tar = None;
+    let mut aux_files = HashMap::new();
+
+    for entry in entries {
+        let mut entry =
+            entry.map_err(caused_by(ErrorKind::InvalidArchive, "Gem tar entry error"))?;
+        let path = entry
+            .path()
+            .map_err(caused_by(ErrorKind::InvalidArchive, "Gem tar path error"))?;
+
+        let path_str = path.to_string_lossy();
+        if path_str == "data.tar.gz" || path_str == "data.tar" {
+            let mut data_tar_bytes = Vec::new();
+            entry
+                .read_to_end(&mut data_tar_bytes)
+                .map_err(caused_by(ErrorKind::InvalidArchive, "Failed to read data.tar.gz"))?;
+            data_tar = Some(data_tar_bytes);
+        } else if path_str == "metadata.gz" {
+            let mut raw = Vec::new();
+            entry
+                .read_to_end(&mut raw)
+                .map_err(caused_by(ErrorKind::InvalidArchive, "Failed to read metadata.gz"))?;
+            let content = decode_gzip_or_raw(&raw, "metadata.gz", budget)?;
+            budget.add_file("metadata.yml", content.len() as u64)?;
+            aux_files.insert("metadata.yml".to_string(), FileMapEntry::file(content));
+        } else if path_str == "checksums.yaml.gz" || path_str == "checksums.yaml" {
+            let mut raw = Vec::new();
+            entry
+                .read_to_end(&mut raw)
+                .map_err(caused_by(ErrorKind::InvalidArchive, "Failed to read checksums.yaml"))?;
+            let content = decode_gzip_or_raw(&raw, "checksums.yaml", budget)?;
+            budget.add_file("checksums.yaml", content.len() as u64)?;
+            aux_files.insert("checksums.yaml".to_string(), FileMapEntry::file(content));
+        }
+    }
+
+    if let Some(data_tar_bytes) = data_tar {
+        let mut data_files = extract_archive_bytes(&data_tar_bytes, budget)?;
+        data_files.extend(aux_files);
+        Ok(data_files)
+    } else {
+        Err(PackageError::new(
+            ErrorKind::MissingEntry,
+            "data.tar.gz or data.tar not found in .gem file",
+        ))
+    }
+}
+
+fn extract_hex_bytes(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let mut archive = Archive::new(Cursor::new(bytes));
+    let entries = archive
+        .entries()
+        .map_err(caused_by(ErrorKind::InvalidArchive, "Hex tar parsing failed"))?;
+
+    let mut contents_tar = None;
+    let mut aux_files = HashMap::new();
+
+    for entry in entries {
+        let mut entry =
+            entry.map_err(caused_by(ErrorKind::InvalidArchive, "Hex tar entry error"))?;
+        let path = entry
+            .path()
+            .map_err(caused_by(ErrorKind::InvalidArchive, "Hex tar path error"))?;
+
+        let path_str = path.to_string_lossy();
+        if path_str == "contents.tar.gz" {
+            let mut contents_tar_bytes = Vec::new();
+            entry
+                .read_to_end(&mut contents_tar_bytes)
+                .map_err(caused_by(ErrorKind::InvalidArchive, "Failed to read contents.tar.gz"))?;
+            contents_tar = Some(contents_tar_bytes);
+        } else if path_str == "metadata.config" {
+            let mut content = Vec::new();
+            entry
+                .read_to_end(&mut content)
+                .map_err(caused_by(ErrorKind::InvalidArchive, "Failed to read metadata.config"))?;
+            budget.add_file("metadata.config", content.len() as u64)?;
+            aux_files.insert("metadata.config".to_string(), FileMapEntry::file(content));
+        }
+    }
+
+    if let Some(contents_tar_bytes) = contents_tar {
+        // The inner tarball is flat, so there is no root directory to strip.
+        let mut files = extract_archive_entries(&contents_tar_bytes, budget)?;
+        files.extend(aux_files);
+        Ok(files)
+    } else {
+        Err(PackageError::new(
+            ErrorKind::MissingEntry,
+            "contents.tar.gz not found in Hex tarball",
+        ))
+    }
+}
+
+fn extract_archive_bytes(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    Ok(strip_common_root(extract_archive_entries(bytes, budget)?))
+}
+
+/// Extracts paths exactly as stored. Used for formats whose archives are flat,
+/// where a lone top-level directory is content rather than a wrapper.
+fn extract_archive_entries(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let Some(compression) = Compression::detect(bytes) else {
+        return parse_container_bytes(bytes, budget);
+    };
+    // One layer only: a stream that decompresses to another stream is either a
+    // bomb or a quine, and recursing would hold every layer in memory at once.
+    let decompressed = decompress(bytes, compression, "archive", budget)?;
+    if Compression::detect(&decompressed).is_some() {
+        return Err(limit_exceeded("Archive is compressed more than once".to_string()));
+    }
+    parse_container_bytes(&decompressed, budget)
+}
+
+fn parse_container_bytes(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    if is_zip(bytes) {
+        parse_zip_bytes(bytes, budget)
+    } else {
+        parse_tar_bytes(bytes, budget)
+    }
+}
+
+/// Go module zips nest every file under `<module>@<version>/`, where the module
+/// path itself contains slashes, so the prefix is stripped by name.
+fn extract_go_module_zip(
+    bytes: &[u8],
+    module: &str,
+    version: &str,
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let prefix = format!("{module}@{version}/");
+    let files = parse_zip_bytes(bytes, budget)?
+        .into_iter()
+        .filter_map(|(path, entry)| {
+            let relative = path.strip_prefix(&prefix)?;
+            (!relative.is_empty()).then(|| (relative.to_string(), entry))
+        })
+        .collect();
+    Ok(files)
+}
+
+/// A .nupkg is an OPC zip: the `.nuspec` already sits at the top level, next to
+/// packaging parts that say nothing about the package. Those parts are always
+/// dropped; there is no option to keep them, since `nuget pack` regenerates them
+/// (with a random `.psmdcp` name) on every build and they would show as churn.
+fn extract_nupkg_bytes(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let mut files = parse_zip_bytes(bytes, budget)?;
+    files.retain(|path, _| !is_opc_packaging_path(path));
+    if !files.keys().any(|path| path.starts_with("package/")) {
+        files.remove("package");
+    }
+    Ok(files)
+}
+
+fn is_opc_packaging_path(path: &str) -> bool {
+    let in_dir = |dir: &str| {
+        path == dir || path.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/'))
+    };
+    path == "[Content_Types].xml" || in_dir("_rels") || in_dir("package/services")
+}
+
+fn decode_gzip_or_raw(
+    bytes: &[u8],
+    label: &str,
+    budget: &ExtractionBudget<'_>,
+) -> Result<Vec<u8>, PackageError> {
+    if is_gzip(bytes) {
+        decompress(bytes, Compression::Gzip, label, budget)
+    } else {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// Stream compression around a tarball, recognised by its magic bytes.
+#[derive(Clone, Copy)]
+enum Compression {
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+}
+
+impl Compression {
+    fn detect(bytes: &[u8]) -> Option<Self> {
+        if is_gzip(bytes) {
+            Some(Self::Gzip)
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if is_bzip2(bytes) {
+            Some(Self::Bzip2)
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "Gzip",
+            Self::Xz => "XZ",
+            Self::Bzip2 => "Bzip2",
+            Self::Zstd => "Zstandard",
+        }
+    }
+}
+
+/// Decompresses a whole stream, refusing to produce more than the budget has
+/// left. The output counts against the budget, since it stays in memory next to
+/// the files parsed out of it.
+fn decompress(
+    bytes: &[u8],
+    compression: Compression,
+    label: &str,
+    budget: &ExtractionBudget<'_>,
+) -> Result<Vec<u8>, PackageError> {
+    let (allowance, cap) = budget.inflation_allowance();
+    let mut output = LimitedWriter::new(allowance);
+    let result = decompress_into(bytes, compression, &mut output);
+    if output.exceeded {
+        return Err(budget.cap_error(cap, label));
+    }
+    result.map_err(caused_by(
+        ErrorKind::Decompression,
+        format!("{} decompression failed for {label}", compression.name()),
+    ))?;
+    let size = output.buffer.len() as u64;
+    budget.add_inflated(label, size)?;
+    budget.add_total(size)?;
+    Ok(output.buffer)
+}
+
+/// Write sink that fails instead of growing past `limit` bytes.
+struct LimitedWriter {
+    buffer: Vec<u8>,
+    limit: u64,
+    exceeded: bool,
+}
+
+impl LimitedWriter {
+    fn new(limit: u64) -> Self {
+        Self { buffer: Vec::new(), limit, exceeded: false }
+    }
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() as u64 + data.len() as u64 > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other("decompressed size limit exceeded"));
+        }
+        self.buffer.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Running totals for one package, checked against its `ExtractionLimits` across
+/// nested archives such as a gem's `data.tar.gz`.
+struct ExtractionBudget<'a> {
+    limits: &'a ExtractionLimits,
+    /// Bytes downloaded or read from disk, the base for the compression ratio.
+    input_size: Cell<u64>,
+    /// Bytes produced by decompression, whether streams or zip entries.
+    inflated: Cell<u64>,
+    /// Bytes held in memory: extracted files plus decompressed streams.
+    total_size: Cell<u64>,
+    entries: Cell<usize>,
+}
+
+impl<'a> ExtractionBudget<'a> {
+    /// Decompressing up to this much always passes the ratio check, since tiny
+    /// highly repetitive files compress far beyond any sensible ratio.
+    const RATIO_EXEMPT_SIZE: u64 = 1 << 20;
+
+    fn new(limits: &'a ExtractionLimits) -> Self {
+        Self {
+            limits,
+            input_size: Cell::new(0),
+            inflated: Cell::new(0),
+            total_size: Cell::new(0),
+            entries: Cell::new(0),
+        }
+    }
+
+    fn add_input(&self, size: u64) {
+        self.input_size.set(self.input_size.get().saturating_add(size));
+    }
+
+    fn add_entry(&self) -> Result<(), PackageError> {
+        let entries = self.entries.get() + 1;
+        if entries > self.limits.max_entries {
+            let message = format!("Archive has more than {} entries", self.limits.max_entries);
+            return Err(limit_exceeded(message));
+        }
+        self.entries.set(entries);
+        Ok(())
+    }
+
+    /// Reserves room for a file before its contents are read.
+    fn add_file(&self, path: &str, size: u64) -> Result<(), PackageError> {
+        if size > self.limits.max_file_size {
+            return Err(limit_exceeded(format!(
+                "{path} is {size} bytes, over the {}-byte limit per file",
+                self.limits.max_file_size
+            )));
+        }
+        self.add_total(size)
+    }
+
+    fn add_total(&self, size: u64) -> Result<(), PackageError> {
+        let total_size = self.total_size.get().saturating_add(size);
+        if total_size > self.limits.max_total_size {
+            return Err(self.total_size_error());
+        }
+        self.total_size.set(total_size);
+        Ok(())
+    }
+
+    fn total_size_error(&self) -> PackageError {
+        limit_exceeded(format!("Package unpacks to more than {} bytes", self.limits.max_total_size))
+    }
+
+    /// Most that decompression may produce across all layers and entries, as a
+    /// ratio of the original input rather than of each layer's own input.
+    fn max_inflated(&self) -> u64 {
+        self.input_size
+            .get()
+            .saturating_mul(self.limits.max_compression_ratio)
+            .max(Self::RATIO_EXEMPT_SIZE)
+    }
+
+    /// Bytes a stream may still decompress to, and which cap that leaves it.
+    fn inflation_allowance(&self) -> (u64, InflationCap) {
+        let by_ratio = self.max_inflated().saturating_sub(self.inflated.get());
+        let by_total = self.limits.max_total_size.saturating_sub(self.total_size.get());
+        if by_total < by_ratio {
+            (by_total, InflationCap::TotalSize)
+        } else {
+            (by_ratio, InflationCap::Ratio)
+        }
+    }
+
+    /// Records decompressed output against the compression ratio.
+    fn add_inflated(&self, label: &str, size: u64) -> Result<(), PackageError> {
+        let inflated = self.inflated.get().saturating_add(size);
+        if inflated > self.max_inflated() {
+            return Err(self.inflation_error(label));
+        }
+        self.inflated.set(inflated);
+        Ok(())
+    }
+
+    fn inflation_error(&self, label: &str) -> PackageError {
+        limit_exceeded(format!(
+            "{label} decompresses past the limit for a {}-byte package",
+            self.input_size.get()
+        ))
+    }
+
+    fn cap_error(&self, cap: InflationCap, label: &str) -> PackageError {
+        match cap {
+            InflationCap::Ratio => self.inflation_error(label),
+            InflationCap::TotalSize => self.total_size_error(),
+        }
+    }
+}
+
+/// The limit that bounds how far a stream may decompress.
+#[derive(Clone, Copy)]
+enum InflationCap {
+    Ratio,
+    TotalSize,
+}
+
+fn limit_exceeded(message: String) -> PackageError {
+    PackageError::new(ErrorKind::LimitExceeded, message)
+}
+
+fn decompress_into(
+    bytes: &[u8],
+    compression: Compression,
+    output: &mut impl Write,
+) -> Result<(), String> {
+    match compression {
+        Compression::Gzip => {
+            io::copy(&mut GzDecoder::new(bytes), output).map_err(|err| err.to_string())?;
+        }
+        Compression::Xz => {
+            lzma_rs::xz_decompress(&mut Cursor::new(bytes), output)
+                .map_err(|err| err.to_string())?;
+        }
+        Compression::Bzip2 => {
+            // Parallel compressors such as pbzip2 emit several concatenated streams.
+            let mut decoder = bzip2::read::MultiBzDecoder::new(bytes);
+            io::copy(&mut decoder, output).map_err(|err| err.to_string())?;
+        }
+        Compression::Zstd => {
+            // A zstd stream may hold several frames; each decoder consumes one.
+            let mut input = bytes;
+            while !input.is_empty() {
+                let mut decoder = StreamingDecoder::new(&mut input).map_err(|err| err.to_string())?;
+                io::copy(&mut decoder, output).map_err(|err| err.to_string())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_tar_bytes(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let mut archive = Archive::new(Cursor::new(bytes));
+    let mut files = HashMap::new();
+    let entries = archive
+        .entries()
+        .map_err(caused_by(ErrorKind::InvalidArchive, "Tar parsing failed"))?;
+
+    for entry in entries {
+        let mut entry = entry.map_err(caused_by(ErrorKind::InvalidArchive, "Tar entry error"))?;
+        budget.add_entry()?;
+        let entry_type = entry.header().entry_type();
+        let path = entry
+            .path()
+            .map_err(caused_by(ErrorKind::InvalidArchive, "Tar path error"))?;
+        let normalized = normalize_path(&path.to_string_lossy(), entry_type.is_dir());
+        if normalized.is_empty() {
+            continue;
+        }
+
+        if entry_type.is_dir() {
+            files.insert(normalized, FileMapEntry::directory());
+        } else if entry_type.is_file() {
+            budget.add_file(&normalized, entry.size())?;
+            let mut contents = Vec::new();
+            entry
+                .read_to_end(&mut contents)
+                .map_err(caused_by(ErrorKind::InvalidArchive, "Tar read failed"))?;
+            files.insert(normalized, FileMapEntry::file(contents));
+        }
+    }
+
+    ensure_directories(&mut files);
+    Ok(files)
+}
+
+fn parse_zip_bytes(
+    bytes: &[u8],
+    budget: &ExtractionBudget<'_>,
+) -> Result<HashMap<String, FileMapEntry>, PackageError> {
+    let reader = Cursor::new(bytes);
+    let mut archive = ZipArchive::new(reader)
+        .map_err(caused_by(ErrorKind::InvalidArchive, "Zip parsing failed"))?;
+    let mut files = HashMap::new();
+
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(caused_by(ErrorKind::InvalidArchive, "Zip entry error"))?;
+        budget.add_entry()?;
+        let normalized = normalize_path(entry.name(), entry.is_dir());
+        if normalized.is_empty() {
+            continue;
+        }
+
+        if entry.is_dir() {
+            files.insert(normalized, FileMapEntry::directory());
+        } else {
+            // The declared size is checked up front and then enforced while
+            // reading, since a crafted header can understate it.
+            let size = entry.size();
+            budget.add_file(&normalized, size)?;
+            if entry.compression() != CompressionMethod::Stored {
+                budget.add_inflated(&normalized, size)?;
+            }
+            let mut contents = Vec::new();
+            entry
+                .by_ref()
+                .take(size + 1)
+                .read_to_end(&mut contents)
+                .map_err(caused_by(ErrorKind::InvalidArchive, "Zip read failed"))?;
+            if contents.len() as u64 > size {
+                return Err(PackageError::new(
+                    ErrorKind::InvalidArchive,
+                    format!("{normalized} is larger than its zip header declares"),
+                ));
+            }
+            files.insert(normalized, FileMapEntry::file(contents));
+        }
+    }
+
+    ensure_directories(&mut files);
+    Ok(files)
+}
+
+fn normalize_path(path: &str, is_directory: bool) -> String {
+    let normalized_path = path.replace('\\', "/");
+    let mut trimmed = normalized_path.as_str();
+    while trimmed.starts_with("./") {
+        trimmed = &trimmed[2..];
+    }
+    let trimmed = trimmed.trim_start_matches('/');
+    if trimmed.is_empty() || trimmed == "." {
+        return String::new();
+    }
+    let normalized = if is_directory {
+        trimmed.trim_end_matches('/').to_string()
+    } else {
+        trimmed.to_string()
+    };
+    normalized
+}
+
+fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0] == 0x1f && bytes[1] == 0x8b
+}
+
+/// `BZh`, a block size digit, then the magic of either the first block or the
+/// end of an empty stream. The three letters alone are too common in tar names.
+fn is_bzip2(bytes: &[u8]) -> bool {
+    const BLOCK_MAGIC: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+    const END_MAGIC: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+    bytes.len() >= 10
+        && bytes.starts_with(b"BZh")
+        && (b'1'..=b'9').contains(&bytes[3])
+        && (bytes[4..10] == BLOCK_MAGIC || bytes[4..10] == END_MAGIC)
+}
+
+fn is_zip(bytes: &[u8]) -> bool {
+    bytes.len() >= 4
+        && ((bytes[0] == 0x50 && bytes[1] == 0x4b && bytes[2] == 0x03 && bytes[3] == 0x04)
+            || (bytes[0] == 0x50 && bytes[1] == 0x4b && bytes[2] == 0x05 && bytes[3] == 0x06)
+            || (bytes[0] == 0x50 && bytes[1] == 0x4b && bytes[2] == 0x07 && bytes[3] == 0x08))
+}
+
+fn ensure_directories(files: &mut HashMap<String, FileMapEntry>) {
+    let paths: Vec<String> = files.keys().cloned().collect();
+    for path in paths {
+        let mut current = String::new();
+        for part in path.split('/').take_while(|part| !part.is_empty()) {
+            if !current.is_empty() {
+                current.push('/');
+            }
+            current.push_str(part);
+            if !files.contains_key(&current) {
+                files.insert(current.clone(), FileMapEntry::directory());
+            }
+        }
+    }
+}
+
+fn strip_common_root(mut files: HashMap<String, FileMapEntry>) -> HashMap<String, FileMapEntry> {
+    let paths: Vec<String> = files.keys().cloned().collect();
+    if paths.is_empty() {
+        return files;
+    }
+
+    let mut top_level = HashSet::new();
+    for path in &paths {
+        if let Some(first) = path.split('/').next() {
+            if !first.is_empty() {
+                top_level.insert(first.to_string());
+            }
+        }
+    }
+
+    if top_level.len() != 1 {
+        return files;
+    }
+
+    let root = top_level.into_iter().next().unwrap();
+    match files.get(&root) {
+        Some(entry) if matches!(entry.file_type, FileType::Directory) => {}
+        _ => return files,
+    }
+
+    let prefix = format!("{root}/");
+    let mut new_files = HashMap::new();
+    let mut has_files = false;
+    for path in paths {
+        if path == root {
+            continue;
+        }
+        if let Some(new_path) = path.strip_prefix(&prefix) {
+            if !new_path.is_empty() {
+                if let Some(entry) = files.remove(&path) {
+                    new_files.insert(new_path.to_string(), entry);
+                    has_files = true;
+                }
+            }
+        }
+    }
+
+    if has_files {
+        new_files
+    } else {
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression as GzLevel;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+
+    fn tar_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *contents).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), GzLevel::best());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zstd(bytes: &[u8]) -> Vec<u8> {
+        ruzstd::encoding::compress_to_vec(bytes, ruzstd::encoding::CompressionLevel::Fastest)
+    }
+
+    fn zip_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, contents) in files {
+            writer.start_file(*path, SimpleFileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn extract(bytes: &[u8], limits: &ExtractionLimits) -> Result<Vec<String>, PackageError> {
+        let files = extract_local_archive("package.tgz", bytes, limits)?;
+        let mut paths: Vec<String> = files.into_keys().collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    fn error_kind<T: std::fmt::Debug>(result: Result<T, PackageError>) -> ErrorKind {
+        result.expect_err("expected an error").kind
+    }
+
+    fn file_map(paths: &[&str]) -> HashMap<String, FileMapEntry> {
+        let mut files: HashMap<String, FileMapEntry> = paths
+            .iter()
+            .map(|path| (path.to_string(), FileMapEntry::file(Vec::new())))
+            .collect();
+        ensure_directories(&mut files);
+        files
+    }
+
+    fn sorted_paths(files: HashMap<String, FileMapEntry>) -> Vec<String> {
+        let mut paths: Vec<String> = files.into_keys().collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn strips_a_single_root_directory() {
+        let files = strip_common_root(file_map(&["package/a.js", "package/lib/b.js"]));
+        assert_eq!(sorted_paths(files), ["a.js", "lib", "lib/b.js"]);
+    }
+
+    #[test]
+    fn keeps_multiple_roots_and_lone_files() {
+        let files = strip_common_root(file_map(&["a/x.js", "b/y.js"]));
+        assert_eq!(sorted_paths(files), ["a", "a/x.js", "b", "b/y.js"]);
+        let files = strip_common_root(file_map(&["README.md"]));
+        assert_eq!(sorted_paths(files), ["README.md"]);
+    }
+
+    #[test]
+    fn escapes_upper_case_in_go_paths() {
+        assert_eq!(escape_go_path("github.com/Azure/azure-sdk"), "github.com/!azure/azure-sdk");
+        assert_eq!(escape_go_path("v1.0.0-RC1"), "v1.0.0-!r!c1");
+        assert_eq!(escape_go_path("golang.org/x/text"), "golang.org/x/text");
+    }
+
+    #[test]
+    fn normalizes_archive_paths() {
+        assert_eq!(normalize_path("./package/index.js", false), "package/index.js");
+        assert_eq!(normalize_path("././a.txt", false), "a.txt");
+        assert_eq!(normalize_path("/abs/file", false), "abs/file");
+        assert_eq!(normalize_path("dir\\sub\\file.txt", false), "dir/sub/file.txt");
+        assert_eq!(normalize_path("lib/", true), "lib");
+        assert_eq!(normalize_path("./", true), "");
+        assert_eq!(normalize_path(".", true), "");
+    }
+
+    #[test]
+    fn extracts_a_single_compressed_layer() {
+        let archive = gzip(&tar_bytes(&[("pkg/a.txt", b"a"), ("pkg/b.txt", b"b")]));
+        let paths = extract(&archive, &ExtractionLimits::default()).unwrap();
+        assert_eq!(paths, ["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn rejects_nested_compression() {
+        let archive = gzip(&gzip(&tar_bytes(&[("a.txt", b"a")])));
+        let kind = error_kind(extract(&archive, &ExtractionLimits::default()));
+        assert_eq!(kind, ErrorKind::LimitExceeded);
+    }
+
+    #[test]
+    fn ratio_is_measured_against_the_original_input() {
+        let zeros = vec![0; 4 << 20];
+        let archive = gzip(&tar_bytes(&[("zeros", &zeros)]));
+        let limits = ExtractionLimits { max_compression_ratio: 2, ..ExtractionLimits::default() };
+        assert_eq!(error_kind(extract(&archive, &limits)), ErrorKind::LimitExceeded);
+    }
+
+    #[test]
+    fn decompressed_streams_count_towards_the_total() {
+        // Multiplicative hashing gives bytes that gzip cannot shrink much.
+        let contents: Vec<u8> =
+            (0..400_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+        let tar = tar_bytes(&[("data.bin", &contents)]);
+        let limits = ExtractionLimits { max_total_size: 600_000, ..ExtractionLimits::default() };
+        assert!(extract(&tar, &limits).is_ok());
+        assert_eq!(error_kind(extract(&gzip(&tar), &limits)), ErrorKind::LimitExceeded);
+    }
+
+    #[test]
+    fn names_the_cap_that_stops_decompression() {
+        let archive = gzip(&tar_bytes(&[("zeros", &vec![0; 4 << 20])]));
+        let message = |limits: &ExtractionLimits| extract(&archive, limits).unwrap_err().message;
+
+        let limits = ExtractionLimits { max_total_size: 100_000, ..ExtractionLimits::default() };
+        assert_eq!(message(&limits), "Package unpacks to more than 100000 bytes");
+        let limits = ExtractionLimits { max_compression_ratio: 2, ..ExtractionLimits::default() };
+        assert!(message(&limits).contains("decompresses past the limit"));
+    }
+
+    #[test]
+    fn enforces_file_size_and_entry_count() {
+        let tar = tar_bytes(&[("a.txt", &[b'a'; 100]), ("b.txt", b"b"), ("c.txt", b"c")]);
+        let limits = ExtractionLimits { max_file_size: 10, ..ExtractionLimits::default() };
+        assert_eq!(error_kind(extract(&tar, &limits)), ErrorKind::LimitExceeded);
+        let limits = ExtractionLimits { max_entries: 2, ..ExtractionLimits::default() };
+        assert_eq!(error_kind(extract(&tar, &limits)), ErrorKind::LimitExceeded);
+    }
+
+    #[test]
+    fn rejects_zip_entries_larger_than_declared() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("big.txt", SimpleFileOptions::default()).unwrap();
+        writer.write_all(&[b'x'; 100_000]).unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        // Understate the uncompressed size in both the local and central headers.
+        let understate = |bytes: &mut Vec<u8>, signature: &[u8], offset: usize| {
+            let start = bytes.windows(4).position(|window| window == signature).unwrap();
+            bytes[start + offset..start + offset + 4].copy_from_slice(&10u32.to_le_bytes());
+        };
+        understate(&mut bytes, b"PK\x03\x04", 22);
+        understate(&mut bytes, b"PK\x01\x02", 24);
+
+        let result = extract_local_archive("package.zip", &bytes, &ExtractionLimits::default());
+        assert_eq!(result.expect_err("extraction should fail").kind, ErrorKind::InvalidArchive);
+    }
+
+    #[test]
+    fn selects_packagist_dist_urls() {
+        let versions: Vec<PackagistVersion> = serde_json::from_str(
+            r#"[
+                {
+                    "version": "v2.0.0",
+                    "version_normalized": "2.0.0.0",
+                    "dist": {"type": "zip", "url": "https://example.test/2.0.0.zip"}
+                },
+                {"version": "1.5.0", "version_normalized": "1.5.0.0", "dist": "__unset"}
+            ]"#,
+        )
+        .unwrap();
+        let url = |version| select_packagist_dist_url(&versions, version);
+        for version in ["v2.0.0", "2.0.0", "2.0.0.0"] {
+            assert_eq!(url(version).unwrap(), "https://example.test/2.0.0.zip", "{version}");
+        }
+        assert_eq!(error_kind(url("v1.5.0")), ErrorKind::NoArtifact);
+        assert_eq!(error_kind(url("3.0.0")), ErrorKind::NotFound);
+    }
+
+    fn conda_file(basename: &str, version: &str, subdir: &str, build_number: u64) -> CondaFile {
+        CondaFile {
+            basename: basename.to_string(),
+            version: version.to_string(),
+            attrs: CondaFileAttrs {
+                subdir: subdir.to_string(),
+                build: format!("py_{build_number}"),
+                build_number,
+            },
+        }
+    }
+
+    #[test]
+    fn selects_conda_files_by_subdir_build_and_format() {
+        let files = [
+            conda_file("linux-64/a-1.0-py_3.tar.bz2", "1.0", "linux-64", 3),
+            conda_file("noarch/a-1.0-py_0.conda", "1.0", "noarch", 0),
+            conda_file("noarch/a-1.0-py_1.tar.bz2", "1.0", "noarch", 1),
+            conda_file("noarch/a-1.0-py_1.conda", "1.0", "noarch", 1),
+            conda_file("osx-arm64/a-1.0-py_2.conda", "1.0", "osx-arm64", 2),
+            conda_file("linux-64/a-0.9-py_0.tar.bz2", "0.9", "linux-64", 0),
+            conda_file("osx-arm64/a-0.9-py_0.conda", "0.9", "osx-arm64", 0),
+        ];
+        let select = |subdir, version| select_conda_file(&files, subdir, version);
+        assert_eq!(select(None, "1.0"), Some("noarch/a-1.0-py_1.conda"));
+        assert_eq!(select(None, "1.0-py_0"), Some("noarch/a-1.0-py_0.conda"));
+        assert_eq!(select(None, "0.9"), Some("linux-64/a-0.9-py_0.tar.bz2"));
+        assert_eq!(select(Some("linux-64"), "1.0"), Some("linux-64/a-1.0-py_3.tar.bz2"));
+        assert_eq!(select(Some("osx-arm64"), "1.0"), Some("osx-arm64/a-1.0-py_2.conda"));
+        assert_eq!(select(Some("win-64"), "1.0"), None);
+        assert_eq!(select(None, "2.0"), None);
+    }
+
+    #[test]
+    fn merges_conda_info_and_payload_tarballs() {
+        let info = zstd(&tar_bytes(&[("info/index.json", b"{}")]));
+        let payload = zstd(&tar_bytes(&[("lib/a.py", b"a"), ("lib/b.py", b"b")]));
+        let conda = zip_bytes(&[
+            ("metadata.json", b"{}"),
+            ("info-a-1.0-py_0.tar.zst", &info),
+            ("pkg-a-1.0-py_0.tar.zst", &payload),
+        ]);
+        let limits = ExtractionLimits::default();
+        let files = extract_local_archive("a-1.0-py_0.conda", &conda, &limits);
+        assert_eq!(
+            sorted_paths(files.unwrap()),
+            ["info", "info/index.json", "lib", "lib/a.py", "lib/b.py"]
+        );
+
+        let conda = zip_bytes(&[("info-a-1.0-py_0.tar.zst", &info)]);
+        let result = extract_local_archive("a-1.0-py_0.conda", &conda, &limits);
+        let err = result.expect_err("a .conda without a payload should fail");
+        assert_eq!(err.kind, ErrorKind::MissingEntry);
+        assert_eq!(err.message, "pkg-*.tar.zst not found in conda package");
+    }
+
+    #[test]
+    fn drops_opc_packaging_parts_from_nupkgs() {
+        let nupkg = zip_bytes(&[
+            ("[Content_Types].xml", b"<Types/>"),
+            ("_rels/.rels", b"<Relationships/>"),
+            ("package/services/metadata/core-properties/0123abcd.psmdcp", b"<coreProperties/>"),
+            ("Example.nuspec", b"<package/>"),
+            ("lib/net8.0/Example.dll", b"MZ"),
+            ("Content_Types.txt", b"not an OPC part"),
+        ]);
+        let limits = ExtractionLimits::default();
+        let files = extract_local_archive("example.1.0.0.nupkg", &nupkg, &limits);
+        // The emptied `package` directory goes as well.
+        assert_eq!(
+            sorted_paths(files.unwrap()),
+            ["Content_Types.txt", "Example.nuspec", "lib", "lib/net8.0", "lib/net8.0/Example.dll"]
+        );
+    }
+
+    #[test]
+    fn strips_the_module_prefix_from_go_module_zips() {
+        let module = "github.com/Azure/go-autorest";
+        let zip = zip_bytes(&[
+            ("github.com/Azure/go-autorest@v1.2.0/go.mod", b"module github.com/Azure/go-autorest"),
+            ("github.com/Azure/go-autorest@v1.2.0/autorest/client.go", b"package autorest"),
+            ("github.com/Azure/go-autorest@v1.1.0/stale.go", b"package stale"),
+        ]);
+        let limits = ExtractionLimits::default();
+        let budget = ExtractionBudget::new(&limits);
+        let files = extract_go_module_zip(&zip, module, "v1.2.0", &budget).unwrap();
+        assert_eq!(sorted_paths(files), ["autorest", "autorest/client.go", "go.mod"]);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::diff::build_diff_tree;
+use crate::types::{
+    DiffFileEntry, DiffOptions, DiffStatus, FileMapEntry, FileType, ProvenanceOptions,
+    ProvenanceReport,
+};
+
+/// Files registries or build steps routinely add to published artifacts. Names
+/// are anchored to the root, since a build directory nested anywhere else is
+/// where an injected file would hide. Only non-executable outputs (source maps
+/// and type declarations) are ignored at any depth.
+pub const DEFAULT_PROVENANCE_IGNORES: &[&str] = &[
+    "/dist",
+    "/build",
+    "/out",
+    "*.map",
+    "*.d.ts",
+    "/PKG-INFO",
+    "/*.egg-info",
+    "/src/*.egg-info",
+    "/.cargo_vcs_info.json",
+    "/Cargo.toml.orig",
+    "/metadata.yml",
+    "/checksums.yaml",
+    "/metadata.config",
+];
+
+/// Diffs `source` against `artifact` and flags files that only the artifact ships.
+/// The tree runs from source to artifact, so artifact-only files show as added.
+pub fn compare_provenance(
+    source_files: Rc<HashMap<String, FileMapEntry>>,
+    artifact_files: Rc<HashMap<String, FileMapEntry>>,
+    similarity_threshold: f64,
+    options: DiffOptions,
+    provenance: &ProvenanceOptions,
+) -> (DiffFileEntry, ProvenanceReport) {
+    let tree = build_diff_tree(
+        Rc::clone(&source_files),
+        Rc::clone(&artifact_files),
+        similarity_threshold,
+        options,
+    );
+
+    let mut patterns: Vec<&str> = provenance.ignore_patterns.iter().map(String::as_str).collect();
+    if provenance.default_ignores {
+        patterns.extend(DEFAULT_PROVENANCE_IGNORES);
+    }
+
+    // Decided by path rather than tree status, so a file the rename detection
+    // pairs with a similar source file is still reported.
+    let mut artifact_only = Vec::new();
+    let mut ignored = Vec::new();
+    for (path, entry) in artifact_files.iter() {
+        let in_source = source_files
+            .get(path)
+            .is_some_and(|source| matches!(source.file_type, FileType::File));
+        if !matches!(entry.file_type, FileType::File) || in_source {
+            continue;
+        }
+        if patterns.iter().any(|pattern| glob_matches(pattern, path)) {
+            ignored.push(path.clone());
+        } else {
+            artifact_only.push(path.clone());
+        }
+    }
+    artifact_only.sort();
+    ignored.sort();
+
+    let mut modified = Vec::new();
+    collect_modified(&tree, &mut modified);
+    modified.sort();
+
+    let report = ProvenanceReport {
+        artifact_only,
+        ignored,
+        modified,
+    };
+    (tree, report)
+}
+
+fn collect_modified(entry: &DiffFileEntry, modified: &mut Vec<String>) {
+    match entry.file_type {
+        FileType::File => {
+            if matches!(entry.status, DiffStatus::Modified) {
+                modified.push(entry.path.clone());
+            }
+        }
+        FileType::Directory => {
+            for child in entry.children.iter().flatten() {
+                collect_modified(child, modified);
+            }
+        }
+    }
+}
+
+/// Matches `path` against a gitignore-style glob. `*` and `?` stay within one
+/// component and `**` spans any number of them. A pattern without a `/` matches
+/// any component, and a match on a directory covers everything below it.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+    let path_segments: Vec<&str> = path.split('/').collect();
+
+    if !pattern.contains('/') {
+        return path_segments.iter().any(|segment| segment_matches(pattern, segment));
+    }
+
+    let pattern_segments: Vec<&str> = pattern.trim_start_matches('/').split('/').collect();
+    (1..=path_segments.len())
+        .any(|len| segments_match(&pattern_segments, &path_segments[..len]))
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(segment, path_rest)| {
+            segment_matches(first, segment) && segments_match(rest, path_rest)
+        }),
+    }
+}
+
+/// Wildcard match within a single path component.
+fn segment_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` swallow one more character and retry.
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_and_question_mark_stay_within_a_component() {
+        assert!(glob_matches("*.map", "index.js.map"));
+        assert!(glob_matches("file?.txt", "file1.txt"));
+        assert!(!glob_matches("file?.txt", "file10.txt"));
+        assert!(glob_matches("/lib/*.js", "lib/index.js"));
+        assert!(!glob_matches("/lib/*.js", "lib/sub/index.js"));
+        assert!(glob_matches("/a*c*e", "abcde"));
+        assert!(!glob_matches("/a*c*e", "abcdf"));
+    }
+
+    #[test]
+    fn double_star_spans_components() {
+        assert!(glob_matches("/lib/**/*.js", "lib/index.js"));
+        assert!(glob_matches("/lib/**/*.js", "lib/a/b/index.js"));
+        assert!(!glob_matches("/lib/**/*.js", "src/index.js"));
+        assert!(glob_matches("**/fixtures", "test/unit/fixtures/data.json"));
+    }
+
+    #[test]
+    fn anchored_patterns_match_from_the_root() {
+        assert!(glob_matches("/dist", "dist"));
+        assert!(glob_matches("/dist", "dist/index.js"));
+        assert!(glob_matches("/dist/", "dist/index.js"));
+        assert!(!glob_matches("/dist", "lib/dist/index.js"));
+        assert!(!glob_matches("/dist", "distribution/index.js"));
+        assert!(glob_matches("docs/api", "docs/api/index.html"));
+        assert!(!glob_matches("docs/api", "src/docs/api/index.html"));
+    }
+
+    #[test]
+    fn unanchored_patterns_match_any_component() {
+        assert!(glob_matches("node_modules", "node_modules/a/index.js"));
+        assert!(glob_matches("node_modules", "packages/x/node_modules/a/index.js"));
+        assert!(glob_matches("*.d.ts", "types/deep/index.d.ts"));
+        assert!(!glob_matches("node_modules", "node_modules_backup/index.js"));
+    }
+
+    #[test]
+    fn default_ignores_do_not_hide_nested_build_directories() {
+        let files = |paths: &[&str]| {
+            let files: HashMap<String, FileMapEntry> = paths
+                .iter()
+                .map(|path| (path.to_string(), FileMapEntry::file(b"x".to_vec())))
+                .collect();
+            Rc::new(files)
+        };
+        let source = files(&["lib/index.js"]);
+        let artifact = files(&["lib/index.js", "dist/index.js", "lib/out/payload.js"]);
+        let (_, report) = compare_provenance(
+            source,
+            artifact,
+            0.75,
+            DiffOptions::default(),
+            &ProvenanceOptions::default(),
+        );
+        assert_eq!(report.artifact_only, ["lib/out/payload.js"]);
+        assert_eq!(report.ignored, ["dist/index.js"]);
+    }
+
+    #[test]
+    fn default_ignores_cover_hex_metadata_only_at_the_root() {
+        let ignored = |path: &str| {
+            DEFAULT_PROVENANCE_IGNORES.iter().any(|pattern| glob_matches(pattern, path))
+        };
+        assert!(ignored("metadata.config"));
+        assert!(!ignored("lib/metadata.config"));
+    }
+}
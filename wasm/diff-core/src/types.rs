@@ -0,0 +1,324 @@
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    Added,
+    Removed,
+    Modified,
+    Unchanged,
+    Renamed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileType {
+    File,
+    Directory,
+}
+
+/// Raw file bytes, shared so cached packages and diff trees never copy them.
+/// As in git, a file is binary only if it has a NUL byte near the start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileContent {
+    Text(Rc<str>),
+    /// Text in another encoding, such as Latin-1. Line diffs use the lossy UTF-8
+    /// decoding, while hashes and equality use the raw bytes.
+    Lossy { raw: Rc<[u8]>, text: Rc<str> },
+    Binary(Rc<[u8]>),
+}
+
+/// How far into a file to look for NUL bytes, as git does.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+impl FileContent {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        if bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+            return FileContent::Binary(bytes.into());
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => FileContent::Text(text.into()),
+            Err(err) => {
+                let raw = err.into_bytes();
+                let text = String::from_utf8_lossy(&raw).into();
+                FileContent::Lossy { raw: raw.into(), text }
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            FileContent::Text(text) => text.as_bytes(),
+            FileContent::Lossy { raw, .. } => raw,
+            FileContent::Binary(bytes) => bytes,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            FileContent::Text(text) | FileContent::Lossy { text, .. } => Some(text),
+            FileContent::Binary(_) => None,
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, FileContent::Binary(_))
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.as_bytes().is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMapEntry {
+    #[serde(rename = "type")]
+    pub file_type: FileType,
+    pub content: FileContent,
+}
+
+impl FileMapEntry {
+    pub fn file(bytes: Vec<u8>) -> Self {
+        FileMapEntry {
+            file_type: FileType::File,
+            content: FileContent::from_bytes(bytes),
+        }
+    }
+
+    pub fn directory() -> Self {
+        FileMapEntry {
+            file_type: FileType::Directory,
+            content: FileContent::Text("".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffFileEntry {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    #[serde(rename = "type")]
+    pub file_type: FileType,
+    pub status: DiffStatus,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<DiffFileEntry>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InlineMode {
+    None,
+    #[default]
+    Words,
+    Chars,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffAlgorithm {
+    #[default]
+    Myers,
+    Patience,
+    Lcs,
+    Histogram,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiffOptions {
+    pub algorithm: DiffAlgorithm,
+    pub inline_mode: InlineMode,
+    /// Ignore all whitespace when comparing lines (`git diff -w`).
+    pub ignore_whitespace: bool,
+    /// Ignore changes in the amount of whitespace (`git diff -b`).
+    pub ignore_whitespace_amount: bool,
+    /// Ignore changes whose lines are all blank.
+    pub ignore_blank_lines: bool,
+    /// Treat CRLF and CR line endings as LF.
+    pub normalize_eol: bool,
+}
+
+/// Byte range inside a line's content (excluding the line terminator).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InlineSpan {
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineLineChange {
+    pub tag: LineTag,
+    /// 1-based line number in the old file for deletions, in the new file for insertions.
+    pub line: u32,
+    pub spans: Vec<InlineSpan>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub tag: LineTag,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<u32>,
+    /// Line content without its trailing `\n`.
+    pub content: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub missing_newline: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spans: Option<Vec<InlineSpan>>,
+}
+
+/// A unified-diff hunk; starts and lengths follow the `@@ -a,b +c,d @@` convention.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Summary shown instead of a line diff when either side is binary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinaryDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_size: Option<u64>,
+    /// Hex-encoded SHA-256 of the old contents.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_hash: Option<String>,
+}
+
+/// Settings for comparing a published artifact against its source tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProvenanceOptions {
+    /// Globs for artifact-only files that are expected, such as build output.
+    /// Patterns without a `/` match any path component; others match from the root.
+    pub ignore_patterns: Vec<String>,
+    /// Also apply `DEFAULT_PROVENANCE_IGNORES`.
+    pub default_ignores: bool,
+}
+
+impl Default for ProvenanceOptions {
+    fn default() -> Self {
+        Self {
+            ignore_patterns: Vec::new(),
+            default_ignores: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenanceReport {
+    /// Files shipped in the artifact that the source does not contain and no
+    /// ignore pattern accounts for.
+    pub artifact_only: Vec<String>,
+    /// Artifact-only files matched by an ignore pattern.
+    pub ignored: Vec<String>,
+    /// Files present in both whose contents differ.
+    pub modified: Vec<String>,
+}
+
+/// Caps on what a single package may unpack to, so that a hostile or oversized
+/// archive fails with a `LimitExceeded` error instead of exhausting memory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExtractionLimits {
+    /// Bytes held in memory at once: extracted files plus decompressed tarballs.
+    pub max_total_size: u64,
+    /// Uncompressed bytes in any one file.
+    pub max_file_size: u64,
+    /// Archive entries, counting files and directories of nested archives.
+    pub max_entries: usize,
+    /// Largest allowed ratio of all decompressed output, across streams and zip
+    /// entries, to the size of the downloaded package. The first 1 MiB is exempt.
+    pub max_compression_ratio: u64,
+}
+
+impl Default for ExtractionLimits {
+    fn default() -> Self {
+        Self {
+            max_total_size: 512 << 20,
+            max_file_size: 128 << 20,
+            max_entries: 100_000,
+            max_compression_ratio: 200,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_by_nul_bytes_and_keeps_raw_bytes() {
+        let utf8 = FileContent::from_bytes("café\n".as_bytes().to_vec());
+        assert!(matches!(utf8, FileContent::Text(_)));
+
+        let latin1 = FileContent::from_bytes(b"caf\xe9\n".to_vec());
+        assert!(!latin1.is_binary());
+        assert_eq!(latin1.as_text(), Some("caf\u{fffd}\n"));
+        assert_eq!(latin1.as_bytes(), b"caf\xe9\n");
+
+        let binary = FileContent::from_bytes(b"PNG\0\x01".to_vec());
+        assert!(binary.is_binary());
+        assert_eq!(binary.as_text(), None);
+    }
+
+    #[test]
+    fn lossy_files_are_compared_by_bytes() {
+        use std::collections::HashMap;
+
+        use crate::diff::{build_diff_tree, needs_binary_summary};
+
+        let files = |bytes: &[u8]| {
+            let entry = FileMapEntry::file(bytes.to_vec());
+            Rc::new(HashMap::from([("a.txt".to_string(), entry)]))
+        };
+        let status = |from: &[u8], to: &[u8]| {
+            let tree = build_diff_tree(files(from), files(to), 0.75, DiffOptions::default());
+            tree.children.unwrap().remove(0).status
+        };
+        // Both sides decode to "caf\u{fffd}\n", but the bytes differ.
+        assert!(matches!(status(b"caf\xe9\n", b"caf\xe8\n"), DiffStatus::Modified));
+        assert!(matches!(status(b"caf\xe9\n", b"caf\xe9\n"), DiffStatus::Unchanged));
+
+        let from = FileContent::from_bytes(b"caf\xe9\n".to_vec());
+        let to = FileContent::from_bytes(b"caf\xe8\n".to_vec());
+        assert!(needs_binary_summary(Some(&from), Some(&to)));
+        assert!(!needs_binary_summary(Some(&from), Some(&from)));
+    }
+}
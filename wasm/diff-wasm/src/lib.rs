@@ -1,24 +1,37 @@
-mod types;
-mod core;
-mod package;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use serde::Serialize;
-use crate::types::FileMapEntry;
+use diff_core::cache::{ExtractionCache, DEFAULT_CACHE_BUDGET_BYTES};
+use diff_core::error::{ErrorKind, PackageError};
+use diff_core::{diff, package, provenance};
+use diff_core::types::{
+    BinaryDiff, DiffFileEntry, DiffHunk, DiffOptions, ExtractionLimits, FileContent, FileMapEntry,
+    FileType, InlineLineChange, ProvenanceOptions, ProvenanceReport,
+};
 
 #[derive(Clone)]
-struct ActiveDiff {
+struct DiffSession {
     from_key: String,
     to_key: String,
+    options: DiffOptions,
 }
 
 thread_local! {
-    static EXTRACTION_CACHE: RefCell<HashMap<String, HashMap<String, FileMapEntry>>> =
-        RefCell::new(HashMap::new());
-    static ACTIVE_DIFF: RefCell<Option<ActiveDiff>> = RefCell::new(None);
+    static EXTRACTION_CACHE: RefCell<ExtractionCache> =
+        RefCell::new(ExtractionCache::new(DEFAULT_CACHE_BUDGET_BYTES));
+    static DIFF_SESSIONS: RefCell<HashMap<u32, DiffSession>> = RefCell::new(HashMap::new());
+    static NEXT_SESSION_ID: Cell<u32> = const { Cell::new(1) };
+    /// Cache keys of archives handed in by `load_local_archive`. They cannot be
+    /// fetched again, so they stay pinned until released.
+    static LOCAL_ARCHIVE_KEYS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    static EXTRACTION_LIMITS: RefCell<ExtractionLimits> = RefCell::new(ExtractionLimits::default());
 }
 
+/// Pseudo-registry for archives supplied from JS rather than downloaded.
+const LOCAL_REGISTRY: &str = "local";
+
 fn cache_key(registry: &str, pkg: &str, version: &str) -> String {
     format!("{registry}:{pkg}:{version}")
 }
@@ -27,71 +40,274 @@ async fn get_or_fetch_package(
     registry: &str,
     pkg: &str,
     version: &str,
-) -> Result<HashMap<String, FileMapEntry>, JsValue> {
+) -> Result<Rc<HashMap<String, FileMapEntry>>, JsValue> {
     let key = cache_key(registry, pkg, version);
-    if let Some(cached) = EXTRACTION_CACHE.with(|cache| cache.borrow().get(&key).cloned()) {
+    if let Some(cached) = EXTRACTION_CACHE.with(|cache| cache.borrow_mut().get(&key).cloned()) {
         return Ok(cached);
     }
+    if registry == LOCAL_REGISTRY {
+        let message = "Local archive is no longer loaded; supply it again";
+        let err = PackageError::new(ErrorKind::NotFound, message);
+        return Err(package_error_to_js(err.for_package(registry, pkg, version)));
+    }
 
-    let files = package::fetch_and_extract_package(registry, pkg, version).await?;
+    let limits = EXTRACTION_LIMITS.with(|limits| limits.borrow().clone());
+    let files = package::fetch_and_extract_package(registry, pkg, version, &limits)
+        .await
+        .map_err(package_error_to_js)?;
+    let files = Rc::new(files);
+    let pinned = pinned_cache_keys();
     EXTRACTION_CACHE.with(|cache| {
-        cache.borrow_mut().insert(key, files.clone());
+        cache.borrow_mut().insert(key, Rc::clone(&files), &pinned);
     });
     Ok(files)
 }
 
+/// Hands package errors to JS as plain objects so the UI can branch on `kind`.
+fn package_error_to_js(err: PackageError) -> JsValue {
+    err.serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap_or_else(|_| JsValue::from_str(&err.to_string()))
+}
+
+/// Cache keys referenced by open diff sessions or loaded local archives, which
+/// must stay cached.
+fn pinned_cache_keys() -> HashSet<String> {
+    let mut pinned: HashSet<String> = DIFF_SESSIONS.with(|sessions| {
+        sessions
+            .borrow()
+            .values()
+            .flat_map(|session| [session.from_key.clone(), session.to_key.clone()])
+            .collect()
+    });
+    LOCAL_ARCHIVE_KEYS.with(|keys| pinned.extend(keys.borrow().iter().cloned()));
+    pinned
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LocalArchive {
+    registry: &'static str,
+    pkg: String,
+    version: String,
+    file_count: usize,
+}
+
+/// Extracts an archive supplied as bytes (tgz, zip, crate, gem, ...) and caches it
+/// under `local:<file name>:<content hash>`. The returned coordinates work as
+/// either side of `build_diff_tree_for_packages` or `build_provenance_report`.
+#[wasm_bindgen]
+pub fn load_local_archive(file_name: String, bytes: Vec<u8>) -> Result<JsValue, JsValue> {
+    let version = diff::sha256_hex(&bytes)[..16].to_string();
+    let key = cache_key(LOCAL_REGISTRY, &file_name, &version);
+    let limits = EXTRACTION_LIMITS.with(|limits| limits.borrow().clone());
+    let files = package::extract_local_archive(&file_name, &bytes, &limits).map_err(|err| {
+        package_error_to_js(err.for_package(LOCAL_REGISTRY, &file_name, &version))
+    })?;
+    let file_count = files.len();
+
+    LOCAL_ARCHIVE_KEYS.with(|keys| keys.borrow_mut().insert(key.clone()));
+    let pinned = pinned_cache_keys();
+    EXTRACTION_CACHE.with(|cache| cache.borrow_mut().insert(key, Rc::new(files), &pinned));
+
+    let archive = LocalArchive {
+        registry: LOCAL_REGISTRY,
+        pkg: file_name,
+        version,
+        file_count,
+    };
+    Ok(serde_wasm_bindgen::to_value(&archive)?)
+}
+
+/// Unpins a local archive so the cache may evict it once no session uses it.
+#[wasm_bindgen]
+pub fn release_local_archive(pkg: String, version: String) -> bool {
+    let key = cache_key(LOCAL_REGISTRY, &pkg, &version);
+    LOCAL_ARCHIVE_KEYS.with(|keys| keys.borrow_mut().remove(&key))
+}
+
+#[wasm_bindgen]
+pub fn set_cache_budget(budget_bytes: usize) {
+    let pinned = pinned_cache_keys();
+    EXTRACTION_CACHE.with(|cache| cache.borrow_mut().set_budget(budget_bytes, &pinned));
+}
+
+/// Replaces the caps applied to packages extracted from now on. Missing fields
+/// take their defaults; already cached packages are kept.
+#[wasm_bindgen]
+pub fn set_extraction_limits(limits: JsValue) -> Result<(), JsValue> {
+    let limits: ExtractionLimits = serde_wasm_bindgen::from_value(limits)?;
+    EXTRACTION_LIMITS.with(|current| *current.borrow_mut() = limits);
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn get_cache_stats() -> Result<JsValue, JsValue> {
+    let pinned = pinned_cache_keys();
+    let stats = EXTRACTION_CACHE.with(|cache| cache.borrow().stats(&pinned));
+    Ok(serde_wasm_bindgen::to_value(&stats)?)
+}
+
+/// Drops all cached packages that no open diff session uses.
+#[wasm_bindgen]
+pub fn clear_cache() {
+    let pinned = pinned_cache_keys();
+    EXTRACTION_CACHE.with(|cache| cache.borrow_mut().clear(&pinned));
+}
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct DiffResult {
     data: String,
     is_diff: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    inline_changes: Vec<InlineLineChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    binary: Option<BinaryDiff>,
+}
+
+/// Binary files, and text changes that lossy decoding hides, are summarized rather than
+/// diffed line by line.
+fn binary_diff(
+    from_content: Option<&FileContent>,
+    to_content: Option<&FileContent>,
+) -> Option<BinaryDiff> {
+    if !diff::needs_binary_summary(from_content, to_content) {
+        return None;
+    }
+    Some(diff::get_binary_diff(
+        from_content.map(FileContent::as_bytes),
+        to_content.map(FileContent::as_bytes),
+    ))
+}
+
+/// How one file compares between the two sides, before it is shaped into a result.
+enum FileDiff<'a> {
+    Missing,
+    Binary { binary: BinaryDiff, changed: bool },
+    Unchanged(&'a str),
+    Changed(Vec<DiffHunk>),
 }
 
-fn build_diff_result(filename: &str, from_content: Option<&str>, to_content: Option<&str>) -> DiffResult {
+/// Decides binary, unchanged or changed once, for both result shapes to build on.
+fn diff_file<'a>(
+    from_content: Option<&'a FileContent>,
+    to_content: Option<&'a FileContent>,
+    context_lines: usize,
+    options: &DiffOptions,
+) -> FileDiff<'a> {
+    let changed =
+        from_content.map(FileContent::as_bytes) != to_content.map(FileContent::as_bytes);
+    if let Some(binary) = binary_diff(from_content, to_content) {
+        return FileDiff::Binary { binary, changed };
+    }
+
+    let from_content = from_content.and_then(FileContent::as_text);
+    let to_content = to_content.and_then(FileContent::as_text);
     match (from_content, to_content) {
-        (None, None) => DiffResult {
+        (None, None) => FileDiff::Missing,
+        (Some(_), Some(to)) if !changed => FileDiff::Unchanged(to),
+        _ => {
+            let hunks = diff::get_diff_hunks(
+                from_content.unwrap_or(""),
+                to_content.unwrap_or(""),
+                context_lines,
+                options,
+            );
+            match (from_content, to_content) {
+                // Both sides exist but only differ in ways the options ignore.
+                (Some(_), Some(to)) if hunks.is_empty() => FileDiff::Unchanged(to),
+                _ => FileDiff::Changed(hunks),
+            }
+        }
+    }
+}
+
+fn build_diff_result(
+    old_path: &str,
+    new_path: &str,
+    from_content: Option<&FileContent>,
+    to_content: Option<&FileContent>,
+    context_lines: usize,
+    options: &DiffOptions,
+) -> DiffResult {
+    let (from_exists, to_exists) = (from_content.is_some(), to_content.is_some());
+    match diff_file(from_content, to_content, context_lines, options) {
+        FileDiff::Missing => DiffResult {
             data: "File not present in either version.".to_string(),
             is_diff: false,
+            inline_changes: Vec::new(),
+            binary: None,
         },
-        (None, Some(to)) => {
-            let header = format!("--- /dev/null\n+++ to/{filename}");
-            let mut lines = Vec::new();
-            lines.push(header);
-            for line in to.split('\n') {
-                lines.push(format!("+ {line}"));
-            }
-            DiffResult {
-                data: lines.join("\n"),
-                is_diff: true,
-            }
-        }
-        (Some(from), None) => {
-            let header = format!("--- from/{filename}\n+++ /dev/null");
-            let mut lines = Vec::new();
-            lines.push(header);
-            for line in from.split('\n') {
-                lines.push(format!("- {line}"));
-            }
-            DiffResult {
-                data: lines.join("\n"),
-                is_diff: true,
-            }
-        }
-        (Some(from), Some(to)) => {
-            if from == to {
-                DiffResult {
-                    data: to.to_string(),
-                    is_diff: false,
-                }
+        FileDiff::Binary { binary, changed } => DiffResult {
+            data: if changed {
+                diff::render_binary_notice(old_path, new_path, from_exists, to_exists)
             } else {
-                DiffResult {
-                    data: core::get_diff_content(filename, from, to),
-                    is_diff: true,
-                }
-            }
+                String::new()
+            },
+            is_diff: changed,
+            inline_changes: Vec::new(),
+            binary: Some(binary),
+        },
+        FileDiff::Unchanged(text) => DiffResult {
+            data: text.to_string(),
+            is_diff: false,
+            inline_changes: Vec::new(),
+            binary: None,
+        },
+        // One line diff feeds both the rendered text and the inline spans.
+        FileDiff::Changed(hunks) => DiffResult {
+            data: diff::render_unified_diff(old_path, new_path, from_exists, to_exists, &hunks),
+            is_diff: true,
+            inline_changes: diff::get_inline_changes(&hunks),
+            binary: None,
+        },
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StructuredDiffResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_path: Option<String>,
+    is_diff: bool,
+    hunks: Vec<DiffHunk>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    binary: Option<BinaryDiff>,
+}
+
+fn build_structured_diff_result(
+    old_path: &str,
+    new_path: &str,
+    from_content: Option<&FileContent>,
+    to_content: Option<&FileContent>,
+    context_lines: usize,
+    options: &DiffOptions,
+) -> StructuredDiffResult {
+    let file_diff = diff_file(from_content, to_content, context_lines, options);
+    let (is_diff, hunks, binary) = match file_diff {
+        FileDiff::Missing => (false, Vec::new(), None),
+        FileDiff::Binary { binary, changed } => (changed, Vec::new(), Some(binary)),
+        FileDiff::Unchanged(text) => {
+            (false, diff::get_unchanged_hunk(text).into_iter().collect(), None)
         }
+        FileDiff::Changed(hunks) => (true, hunks, None),
+    };
+    StructuredDiffResult {
+        old_path: from_content.map(|_| old_path.to_string()),
+        new_path: to_content.map(|_| new_path.to_string()),
+        is_diff,
+        hunks,
+        binary,
+    }
+}
+
+fn parse_diff_options(options: JsValue) -> Result<Option<DiffOptions>, JsValue> {
+    if options.is_undefined() || options.is_null() {
+        return Ok(None);
     }
+    Ok(Some(serde_wasm_bindgen::from_value(options)?))
 }
 
 #[wasm_bindgen]
@@ -104,6 +320,13 @@ pub async fn prefetch_package(
     Ok(())
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiffTreeResult {
+    session_id: u32,
+    tree: DiffFileEntry,
+}
+
 #[wasm_bindgen]
 pub async fn build_diff_tree_for_package(
     registry: String,
@@ -111,52 +334,238 @@ pub async fn build_diff_tree_for_package(
     from: String,
     to: String,
     similarity_threshold: f64,
+    options: JsValue,
 ) -> Result<JsValue, JsValue> {
-    let from_files = get_or_fetch_package(&registry, &pkg, &from).await?;
-    let to_files = get_or_fetch_package(&registry, &pkg, &to).await?;
-    let tree = core::build_diff_tree(from_files, to_files, similarity_threshold);
-
-    let from_key = cache_key(&registry, &pkg, &from);
-    let to_key = cache_key(&registry, &pkg, &to);
-    ACTIVE_DIFF.with(|state| {
-        *state.borrow_mut() = Some(ActiveDiff { from_key, to_key });
-    });
+    build_diff_tree_for_packages(
+        registry.clone(),
+        pkg.clone(),
+        from,
+        registry,
+        pkg,
+        to,
+        similarity_threshold,
+        options,
+    )
+    .await
+}
+
+/// Like `build_diff_tree_for_package`, but each side names its own registry and
+/// package, e.g. to compare `lodash` with `lodash-es` or a fork with its upstream.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub async fn build_diff_tree_for_packages(
+    from_registry: String,
+    from_pkg: String,
+    from_version: String,
+    to_registry: String,
+    to_pkg: String,
+    to_version: String,
+    similarity_threshold: f64,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options = parse_diff_options(options)?.unwrap_or_default();
+    let session_id = open_diff_session(
+        cache_key(&from_registry, &from_pkg, &from_version),
+        cache_key(&to_registry, &to_pkg, &to_version),
+        options.clone(),
+    );
+
+    let tree = async {
+        let from_files = get_or_fetch_package(&from_registry, &from_pkg, &from_version).await?;
+        let to_files = get_or_fetch_package(&to_registry, &to_pkg, &to_version).await?;
+        Ok::<_, JsValue>(diff::build_diff_tree(
+            from_files,
+            to_files,
+            similarity_threshold,
+            options,
+        ))
+    }
+    .await;
+    let tree = match tree {
+        Ok(tree) => tree,
+        Err(err) => {
+            close_diff_session(session_id);
+            return Err(err);
+        }
+    };
 
-    Ok(serde_wasm_bindgen::to_value(&tree)?)
+    Ok(serde_wasm_bindgen::to_value(&DiffTreeResult { session_id, tree })?)
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProvenanceResult {
+    session_id: u32,
+    tree: DiffFileEntry,
+    report: ProvenanceReport,
+}
+
+/// Compares a published artifact against its source, for example an npm tarball
+/// against the matching `github` tag. The returned session diffs source to artifact.
+#[allow(clippy::too_many_arguments)]
 #[wasm_bindgen]
-pub fn get_diff_for_path(filename: String, old_path: Option<String>) -> Result<JsValue, JsValue> {
-    let active = ACTIVE_DIFF
-        .with(|state| state.borrow().clone())
-        .ok_or_else(|| JsValue::from_str("No active diff context"))?;
-    let from_key = active.from_key;
-    let to_key = active.to_key;
-
-    let from_path = old_path.as_deref().unwrap_or(&filename);
-    let (from_content, to_content) = EXTRACTION_CACHE.with(|cache| {
-        let cache = cache.borrow();
-        let from_content = cache
-            .get(&from_key)
-            .and_then(|files| files.get(from_path))
-            .and_then(|entry| match entry.file_type {
-                crate::types::FileType::File => Some(entry.content.as_str()),
-                crate::types::FileType::Directory => None,
-            });
-        let to_content = cache
-            .get(&to_key)
-            .and_then(|files| files.get(&filename))
-            .and_then(|entry| match entry.file_type {
-                crate::types::FileType::File => Some(entry.content.as_str()),
-                crate::types::FileType::Directory => None,
-            });
-        (from_content.map(str::to_string), to_content.map(str::to_string))
+pub async fn build_provenance_report(
+    registry: String,
+    pkg: String,
+    version: String,
+    source_registry: String,
+    source_pkg: String,
+    source_version: String,
+    similarity_threshold: f64,
+    options: JsValue,
+    provenance_options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options = parse_diff_options(options)?.unwrap_or_default();
+    let provenance_options: ProvenanceOptions =
+        if provenance_options.is_undefined() || provenance_options.is_null() {
+            ProvenanceOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(provenance_options)?
+        };
+    let session_id = open_diff_session(
+        cache_key(&source_registry, &source_pkg, &source_version),
+        cache_key(&registry, &pkg, &version),
+        options.clone(),
+    );
+
+    let result = async {
+        let source_files =
+            get_or_fetch_package(&source_registry, &source_pkg, &source_version).await?;
+        let artifact_files = get_or_fetch_package(&registry, &pkg, &version).await?;
+        Ok::<_, JsValue>(provenance::compare_provenance(
+            source_files,
+            artifact_files,
+            similarity_threshold,
+            options,
+            &provenance_options,
+        ))
+    }
+    .await;
+    let (tree, report) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            close_diff_session(session_id);
+            return Err(err);
+        }
+    };
+
+    Ok(serde_wasm_bindgen::to_value(&ProvenanceResult { session_id, tree, report })?)
+}
+
+/// Registers a session up front so both sides stay pinned in the cache while fetching.
+fn open_diff_session(from_key: String, to_key: String, options: DiffOptions) -> u32 {
+    let session_id = NEXT_SESSION_ID.with(|next| {
+        let id = next.get();
+        next.set(id.wrapping_add(1).max(1));
+        id
     });
+    let session = DiffSession {
+        from_key,
+        to_key,
+        options,
+    };
+    DIFF_SESSIONS.with(|sessions| {
+        sessions.borrow_mut().insert(session_id, session);
+    });
+    session_id
+}
 
-    let result = build_diff_result(
-        &filename,
-        from_content.as_deref(),
-        to_content.as_deref(),
+#[wasm_bindgen]
+pub fn close_diff_session(session_id: u32) -> bool {
+    DIFF_SESSIONS.with(|sessions| sessions.borrow_mut().remove(&session_id).is_some())
+}
+
+fn diff_session(session_id: u32) -> Result<DiffSession, JsValue> {
+    DIFF_SESSIONS
+        .with(|sessions| sessions.borrow().get(&session_id).cloned())
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown diff session: {session_id}")))
+}
+
+fn lookup_contents(
+    session: &DiffSession,
+    from_path: &str,
+    to_path: &str,
+) -> (Option<FileContent>, Option<FileContent>) {
+    EXTRACTION_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let mut file_content = |key: &str, path: &str| {
+            cache
+                .get(key)
+                .and_then(|files| files.get(path))
+                .and_then(|entry| match entry.file_type {
+                    FileType::File => Some(entry.content.clone()),
+                    FileType::Directory => None,
+                })
+        };
+        let from_content = file_content(&session.from_key, from_path);
+        let to_content = file_content(&session.to_key, to_path);
+        (from_content, to_content)
+    })
+}
+
+/// Shapes one file's contents into a diff result.
+type BuildResult<R> =
+    fn(&str, &str, Option<&FileContent>, Option<&FileContent>, usize, &DiffOptions) -> R;
+
+/// Diffs one path of a session and serializes the result `build` shapes.
+fn diff_for_path<R: Serialize>(
+    session_id: u32,
+    filename: &str,
+    old_path: Option<&str>,
+    context_lines: Option<u32>,
+    options: JsValue,
+    build: BuildResult<R>,
+) -> Result<JsValue, JsValue> {
+    let session = diff_session(session_id)?;
+    // Without explicit options, diff with the ones the tree was built with.
+    let options = parse_diff_options(options)?.unwrap_or_else(|| session.options.clone());
+    let from_path = old_path.unwrap_or(filename);
+    let (from_content, to_content) = lookup_contents(&session, from_path, filename);
+
+    let context_lines = context_lines.map_or(diff::DEFAULT_CONTEXT_LINES, |n| n as usize);
+    let result = build(
+        from_path,
+        filename,
+        from_content.as_ref(),
+        to_content.as_ref(),
+        context_lines,
+        &options,
     );
     Ok(serde_wasm_bindgen::to_value(&result)?)
 }
+
+#[wasm_bindgen]
+pub fn get_diff_for_path(
+    session_id: u32,
+    filename: String,
+    old_path: Option<String>,
+    context_lines: Option<u32>,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    diff_for_path(
+        session_id,
+        &filename,
+        old_path.as_deref(),
+        context_lines,
+        options,
+        build_diff_result,
+    )
+}
+
+#[wasm_bindgen]
+pub fn get_structured_diff_for_path(
+    session_id: u32,
+    filename: String,
+    old_path: Option<String>,
+    context_lines: Option<u32>,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    diff_for_path(
+        session_id,
+        &filename,
+        old_path.as_deref(),
+        context_lines,
+        options,
+        build_structured_diff_result,
+    )
+}
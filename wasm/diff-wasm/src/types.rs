@@ -1,43 +0,0 @@
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum DiffStatus {
-    Added,
-    Removed,
-    Modified,
-    Unchanged,
-    Renamed,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum FileType {
-    File,
-    Directory,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct FileMapEntry {
-    #[serde(rename = "type")]
-    pub file_type: FileType,
-    pub content: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct DiffFileEntry {
-    pub path: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub old_path: Option<String>,
-    #[serde(rename = "type")]
-    pub file_type: FileType,
-    pub status: DiffStatus,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub added: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub removed: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub children: Option<Vec<DiffFileEntry>>,
-}
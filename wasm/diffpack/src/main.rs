@@ -0,0 +1,377 @@
+//! Command-line front end for the diff core.
+//!
+//! Compares two package versions, each given as a local archive or as
+//! `registry:package@version`, and prints a change summary followed by unified
+//! diffs. Exit status follows diff(1): 0 when nothing changed, 1 when something
+//! did and 2 on errors. With `--provenance`, 1 means the artifact ships files the
+//! source lacks.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use clap::{Parser, ValueEnum};
+use diff_core::{diff, package, provenance};
+use diff_core::types::{
+    DiffAlgorithm, DiffFileEntry, DiffOptions, DiffStatus, ExtractionLimits, FileContent,
+    FileMapEntry, FileType, ProvenanceOptions, ProvenanceReport,
+};
+
+#[derive(Parser)]
+#[command(name = "diffpack", version, about = "Compare two versions of a package")]
+struct Args {
+    /// Old version: a local archive or `registry:package@version`
+    from: String,
+    /// New version: a local archive or `registry:package@version`
+    to: String,
+    /// Lines of context around each change
+    #[arg(short = 'U', long = "unified", default_value_t = diff::DEFAULT_CONTEXT_LINES)]
+    context: usize,
+    /// Only print the summary, not the diffs
+    #[arg(long)]
+    stat: bool,
+    /// Line diff algorithm
+    #[arg(long, value_enum, default_value_t = Algorithm::Myers)]
+    algorithm: Algorithm,
+    /// Ignore all whitespace when comparing lines
+    #[arg(short = 'w', long)]
+    ignore_all_space: bool,
+    /// Ignore changes in the amount of whitespace
+    #[arg(short = 'b', long)]
+    ignore_space_change: bool,
+    /// Ignore changes whose lines are all blank
+    #[arg(short = 'B', long)]
+    ignore_blank_lines: bool,
+    /// Treat CRLF and LF line endings as equal
+    #[arg(long)]
+    strip_trailing_cr: bool,
+    /// Minimum similarity for pairing a removed and an added file as a rename
+    #[arg(long, default_value_t = 0.75)]
+    similarity: f64,
+    /// Treat FROM as the source tree and TO as the published artifact, and fail
+    /// when the artifact ships files the source does not contain
+    #[arg(long)]
+    provenance: bool,
+    /// Glob for expected artifact-only files in --provenance mode (repeatable)
+    #[arg(long = "ignore", value_name = "PATTERN", requires = "provenance")]
+    ignore_patterns: Vec<String>,
+    /// Do not apply the built-in build-output ignores in --provenance mode
+    #[arg(long, requires = "provenance")]
+    no_default_ignores: bool,
+    /// Largest total size, in MiB, a package may unpack to
+    #[arg(
+        long,
+        value_name = "MIB",
+        default_value_t = ExtractionLimits::default().max_total_size >> 20
+    )]
+    max_total_size: u64,
+    /// Largest size, in MiB, of any one file in a package
+    #[arg(
+        long,
+        value_name = "MIB",
+        default_value_t = ExtractionLimits::default().max_file_size >> 20
+    )]
+    max_file_size: u64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Algorithm {
+    Myers,
+    Patience,
+    Lcs,
+    Histogram,
+}
+
+impl From<Algorithm> for DiffAlgorithm {
+    fn from(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Myers => DiffAlgorithm::Myers,
+            Algorithm::Patience => DiffAlgorithm::Patience,
+            Algorithm::Lcs => DiffAlgorithm::Lcs,
+            Algorithm::Histogram => DiffAlgorithm::Histogram,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(true) => ExitCode::from(1),
+        Ok(false) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("diffpack: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Prints the comparison and reports whether anything changed.
+fn run(args: &Args) -> Result<bool, String> {
+    let options = DiffOptions {
+        algorithm: args.algorithm.into(),
+        ignore_whitespace: args.ignore_all_space,
+        ignore_whitespace_amount: args.ignore_space_change,
+        ignore_blank_lines: args.ignore_blank_lines,
+        normalize_eol: args.strip_trailing_cr,
+        ..DiffOptions::default()
+    };
+
+    let limits = ExtractionLimits {
+        max_total_size: args.max_total_size.saturating_mul(1 << 20),
+        max_file_size: args.max_file_size.saturating_mul(1 << 20),
+        ..ExtractionLimits::default()
+    };
+    let from_files = Rc::new(load_files(&args.from, &limits)?);
+    let to_files = Rc::new(load_files(&args.to, &limits)?);
+    let (tree, report) = if args.provenance {
+        let provenance_options = ProvenanceOptions {
+            ignore_patterns: args.ignore_patterns.clone(),
+            default_ignores: !args.no_default_ignores,
+        };
+        let (tree, report) = provenance::compare_provenance(
+            Rc::clone(&from_files),
+            Rc::clone(&to_files),
+            args.similarity,
+            options.clone(),
+            &provenance_options,
+        );
+        (tree, Some(report))
+    } else {
+        let tree = diff::build_diff_tree(
+            Rc::clone(&from_files),
+            Rc::clone(&to_files),
+            args.similarity,
+            options.clone(),
+        );
+        (tree, None)
+    };
+
+    let changed = changed_files(&tree);
+    print_summary(&changed);
+
+    if !args.stat && !changed.is_empty() {
+        println!();
+        for entry in &changed {
+            let old_path = entry.old_path.as_deref().unwrap_or(&entry.path);
+            let from = file_content(&from_files, old_path);
+            let to = file_content(&to_files, &entry.path);
+            if matches!(entry.status, DiffStatus::Renamed) {
+                // Git's rename headers, so that even a pure rename shows up in the patch.
+                println!("diff --git from/{old_path} to/{}", entry.path);
+                println!("rename from {old_path}");
+                println!("rename to {}", entry.path);
+            }
+            let diff = render_file_diff(old_path, &entry.path, from, to, args.context, &options);
+            print!("{diff}");
+        }
+    }
+
+    if let Some(report) = &report {
+        print_provenance_report(report);
+    }
+    Ok(found_differences(&tree, report.as_ref()))
+}
+
+/// Whether to exit with status 1: in provenance mode when the artifact ships files
+/// the source lacks, otherwise when anything changed.
+fn found_differences(tree: &DiffFileEntry, report: Option<&ProvenanceReport>) -> bool {
+    match report {
+        Some(report) => !report.artifact_only.is_empty(),
+        None => !matches!(tree.status, DiffStatus::Unchanged),
+    }
+}
+
+/// Reads a local archive, or fetches `registry:package@version` when no such file exists.
+fn load_files(
+    source: &str,
+    limits: &ExtractionLimits,
+) -> Result<HashMap<String, FileMapEntry>, String> {
+    let path = Path::new(source);
+    if path.is_file() {
+        let bytes = std::fs::read(path).map_err(|err| format!("Failed to read {source}: {err}"))?;
+        let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        return package::extract_local_archive(&file_name, &bytes, limits)
+            .map_err(|err| err.to_string());
+    }
+
+    let (registry, pkg, version) = parse_coordinates(source).ok_or_else(|| {
+        format!("{source} is neither a file nor a registry:package@version coordinate")
+    })?;
+    pollster::block_on(package::fetch_and_extract_package(registry, pkg, version, limits))
+        .map_err(|err| err.to_string())
+}
+
+/// Splits `registry:package@version`. The version follows the last `@` so that
+/// scoped npm packages such as `npm:@scope/name@1.0.0` parse.
+fn parse_coordinates(source: &str) -> Option<(&str, &str, &str)> {
+    let (registry, rest) = source.split_once(':')?;
+    let (pkg, version) = rest.rsplit_once('@')?;
+    if registry.is_empty() || pkg.is_empty() || version.is_empty() {
+        return None;
+    }
+    Some((registry, pkg, version))
+}
+
+/// Changed files in tree order, each rename listed once.
+fn changed_files(tree: &DiffFileEntry) -> Vec<&DiffFileEntry> {
+    let mut changed = Vec::new();
+    collect_changed_files(tree, &mut changed);
+    // The tree keeps a rename's source as a removed file; the rename covers it.
+    let rename_sources: HashSet<String> =
+        changed.iter().filter_map(|entry| entry.old_path.clone()).collect();
+    changed.retain(|entry| {
+        !(matches!(entry.status, DiffStatus::Removed) && rename_sources.contains(&entry.path))
+    });
+    changed
+}
+
+fn collect_changed_files<'a>(entry: &'a DiffFileEntry, changed: &mut Vec<&'a DiffFileEntry>) {
+    if matches!(entry.status, DiffStatus::Unchanged) {
+        return;
+    }
+    match entry.file_type {
+        FileType::File => changed.push(entry),
+        FileType::Directory => {
+            for child in entry.children.iter().flatten() {
+                collect_changed_files(child, changed);
+            }
+        }
+    }
+}
+
+fn print_summary(changed: &[&DiffFileEntry]) {
+    if changed.is_empty() {
+        println!("No changes.");
+        return;
+    }
+
+    let mut total_added = 0;
+    let mut total_removed = 0;
+    for entry in changed {
+        let (marker, path) = match (&entry.status, &entry.old_path) {
+            (DiffStatus::Renamed, Some(old_path)) => ('R', format!("{old_path} -> {}", entry.path)),
+            (DiffStatus::Added, _) => ('A', entry.path.clone()),
+            (DiffStatus::Removed, _) => ('D', entry.path.clone()),
+            _ => ('M', entry.path.clone()),
+        };
+        let added = entry.added.unwrap_or(0);
+        let removed = entry.removed.unwrap_or(0);
+        total_added += added;
+        total_removed += removed;
+
+        if entry.binary {
+            println!("{marker} {path} (binary)");
+        } else {
+            println!("{marker} {path} +{added} -{removed}");
+        }
+    }
+
+    let files = if changed.len() == 1 { "file" } else { "files" };
+    println!(
+        "{} {files} changed, {total_added} insertions(+), {total_removed} deletions(-)",
+        changed.len()
+    );
+}
+
+fn print_provenance_report(report: &ProvenanceReport) {
+    println!();
+    if report.artifact_only.is_empty() {
+        println!("No unexpected files in the published artifact.");
+    } else {
+        println!("Files only in the published artifact:");
+        for path in &report.artifact_only {
+            println!("  {path}");
+        }
+    }
+    if !report.ignored.is_empty() {
+        println!("{} artifact-only files matched ignore patterns.", report.ignored.len());
+    }
+}
+
+fn file_content<'a>(
+    files: &'a HashMap<String, FileMapEntry>,
+    path: &str,
+) -> Option<&'a FileContent> {
+    files
+        .get(path)
+        .filter(|entry| matches!(entry.file_type, FileType::File))
+        .map(|entry| &entry.content)
+}
+
+fn render_file_diff(
+    old_path: &str,
+    new_path: &str,
+    from: Option<&FileContent>,
+    to: Option<&FileContent>,
+    context_lines: usize,
+    options: &DiffOptions,
+) -> String {
+    if diff::needs_binary_summary(from, to) {
+        if from.map(FileContent::as_bytes) == to.map(FileContent::as_bytes) {
+            return String::new();
+        }
+        return diff::render_binary_notice(old_path, new_path, from.is_some(), to.is_some());
+    }
+    diff::get_diff_content(
+        old_path,
+        new_path,
+        from.and_then(FileContent::as_text),
+        to.and_then(FileContent::as_text),
+        context_lines,
+        options,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(entries: &[(&str, &str)]) -> Rc<HashMap<String, FileMapEntry>> {
+        let entry = |text: &str| FileMapEntry::file(text.as_bytes().to_vec());
+        Rc::new(entries.iter().map(|(path, text)| (path.to_string(), entry(text))).collect())
+    }
+
+    fn tree(from: &[(&str, &str)], to: &[(&str, &str)]) -> DiffFileEntry {
+        diff::build_diff_tree(files(from), files(to), 0.75, DiffOptions::default())
+    }
+
+    #[test]
+    fn parses_registry_coordinates() {
+        let scoped = parse_coordinates("npm:@scope/name@1.0.0");
+        assert_eq!(scoped, Some(("npm", "@scope/name", "1.0.0")));
+        assert_eq!(parse_coordinates("maven:g:a@1"), Some(("maven", "g:a", "1")));
+        assert_eq!(parse_coordinates("crates:serde@1.0.0"), Some(("crates", "serde", "1.0.0")));
+        for malformed in ["serde@1.0.0", "npm:left-pad", ":pkg@1", "npm:@1", "npm:pkg@"] {
+            assert_eq!(parse_coordinates(malformed), None, "{malformed}");
+        }
+    }
+
+    #[test]
+    fn lists_each_rename_once() {
+        let text: String = (1..=40).map(|n| format!("line {n}\n")).collect();
+        let tree = tree(&[("old.txt", &text), ("gone.txt", "x\n")], &[("new.txt", &text)]);
+        let changed: Vec<_> = changed_files(&tree)
+            .into_iter()
+            .map(|entry| (entry.path.as_str(), entry.old_path.as_deref()))
+            .collect();
+        assert_eq!(changed, [("gone.txt", None), ("new.txt", Some("old.txt"))]);
+    }
+
+    #[test]
+    fn exit_status_follows_changes_or_provenance() {
+        let unchanged = tree(&[("a.txt", "a\n")], &[("a.txt", "a\n")]);
+        let modified = tree(&[("a.txt", "a\n")], &[("a.txt", "b\n")]);
+        assert!(!found_differences(&unchanged, None));
+        assert!(found_differences(&modified, None));
+
+        let report = |artifact_only: &[&str]| ProvenanceReport {
+            artifact_only: artifact_only.iter().map(|path| path.to_string()).collect(),
+            ignored: Vec::new(),
+            modified: Vec::new(),
+        };
+        // In provenance mode only artifact-only files count, not modifications.
+        assert!(!found_differences(&modified, Some(&report(&[]))));
+        assert!(found_differences(&unchanged, Some(&report(&["payload.js"]))));
+    }
+}